@@ -0,0 +1,340 @@
+//! Evaluates constants at compile time, so that later stages see the value
+//! of every constant as a literal.
+
+use super::{Error, ErrorKind};
+use crate::parse::{
+    Ast, BinaryOp, Const, Expression, ExpressionKind, Item, Span, Suffix, Type, TypeKind, UnaryOp,
+    Value,
+};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Replaces the value of every constant by the literal it evaluates to.
+/// Constants may use literals, operators and other constants.
+pub fn evaluate_constants(ast: &Ast) -> Result<Ast, Vec<Error>> {
+    let mut evaluator = Evaluator {
+        constants: ast.constants().map(|c| (c.name.name.as_str(), c)).collect(),
+        states: HashMap::new(),
+        errors: Vec::new(),
+    };
+
+    let mut evaluated = ast.clone();
+    for item in &mut evaluated.items {
+        if let Item::Const(constant) = item {
+            let name = &constant.name;
+            if let Some(value) = evaluator.constant(&name.name, name.span) {
+                constant.value = value.expression(&constant.ty, constant.value.span);
+            }
+        }
+    }
+
+    if evaluator.errors.is_empty() {
+        Ok(evaluated)
+    } else {
+        Err(evaluator.errors)
+    }
+}
+
+/// The value of a constant expression.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+enum Constant {
+    /// Wide enough for any `u64` as well as any `i64`.
+    Integer(i128),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+    Char(char),
+}
+
+impl Constant {
+    /// The literal for this value, a negated one for negative numbers.
+    fn expression(self, ty: &Type, span: Span) -> Expression {
+        let suffix = match &ty.kind {
+            TypeKind::Named(name) => Suffix::from_name(name),
+            _ => None,
+        };
+        let (negative, value) = match self {
+            Constant::Integer(n) => (n < 0, Value::Integer(n.unsigned_abs() as u64, suffix)),
+            Constant::Float(f) => (f.is_sign_negative(), Value::Float(f.abs(), suffix)),
+            Constant::Boolean(b) => (false, Value::Boolean(b)),
+            Constant::String(s) => (false, Value::String(s)),
+            Constant::Char(c) => (false, Value::Char(c)),
+        };
+
+        let value = Expression {
+            kind: ExpressionKind::Value(value),
+            span,
+        };
+        if !negative {
+            return value;
+        }
+        Expression {
+            kind: ExpressionKind::Unary {
+                op: UnaryOp::Negative,
+                operand: Box::new(value),
+            },
+            span,
+        }
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constant::Integer(n) => write!(f, "{}", n),
+            Constant::Float(x) => write!(f, "{:?}", x),
+            Constant::Boolean(b) => write!(f, "{}", b),
+            Constant::String(s) => write!(f, "{:?}", s),
+            Constant::Char(c) => write!(f, "{:?}", c),
+        }
+    }
+}
+
+enum State {
+    Evaluating,
+    /// `None` if evaluation failed, which has been reported already.
+    Done(Option<Constant>),
+}
+
+struct Evaluator<'a> {
+    constants: HashMap<&'a str, &'a Const>,
+    states: HashMap<&'a str, State>,
+    errors: Vec<Error>,
+}
+
+impl<'a> Evaluator<'a> {
+    /// The value of the constant `name`, referred to at `span`.
+    fn constant(&mut self, name: &str, span: Span) -> Option<Constant> {
+        let constant = match self.constants.get(name) {
+            Some(constant) => *constant,
+            None => return self.error(span, ErrorKind::UnknownConstant(name.to_string())),
+        };
+        let name = constant.name.name.as_str();
+        match self.states.get(name) {
+            Some(State::Evaluating) => {
+                return self.error(span, ErrorKind::ConstantCycle(name.to_string()))
+            }
+            Some(State::Done(value)) => return value.clone(),
+            None => {}
+        }
+
+        self.states.insert(name, State::Evaluating);
+        let value = self
+            .evaluate(&constant.value)
+            .and_then(|value| self.fit(value, constant));
+        self.states.insert(name, State::Done(value.clone()));
+        value
+    }
+
+    fn evaluate(&mut self, expression: &Expression) -> Option<Constant> {
+        match &expression.kind {
+            ExpressionKind::Value(value) => Some(match value {
+                Value::Integer(n, _) => Constant::Integer(i128::from(*n)),
+                Value::Float(f, _) => Constant::Float(*f),
+                Value::Boolean(b) => Constant::Boolean(*b),
+                Value::String(s) => Constant::String(s.clone()),
+                Value::Char(c) => Constant::Char(*c),
+            }),
+            ExpressionKind::Literal(literal) if literal.type_arguments.is_empty() => {
+                self.constant(&literal.ident.name, literal.ident.span)
+            }
+            ExpressionKind::Unary { op, operand } => match (op, self.evaluate(operand)?) {
+                (UnaryOp::Not, Constant::Boolean(b)) => Some(Constant::Boolean(!b)),
+                (UnaryOp::Negative, Constant::Integer(n)) => Some(Constant::Integer(-n)),
+                (UnaryOp::Negative, Constant::Float(f)) => Some(Constant::Float(-f)),
+                _ => self.error(expression.span, ErrorKind::ConstantMismatch),
+            },
+            ExpressionKind::Binary { op, lhs, rhs } => {
+                let lhs = self.evaluate(lhs)?;
+                let rhs = self.evaluate(rhs)?;
+                match binary(*op, lhs, rhs) {
+                    Ok(value) => Some(value),
+                    Err(kind) => self.error(expression.span, kind),
+                }
+            }
+            _ => self.error(expression.span, ErrorKind::NotConstant),
+        }
+    }
+
+    /// Checks that a value fits the declared type of its constant. An
+    /// integer declared as a float becomes one.
+    fn fit(&mut self, value: Constant, constant: &Const) -> Option<Constant> {
+        let span = constant.value.span;
+        let name = match &constant.ty.kind {
+            TypeKind::Named(name) => name.as_str(),
+            _ => "",
+        };
+        let suffix = Suffix::from_name(name);
+        let value = match (value, suffix) {
+            (Constant::Integer(n), Some(suffix)) if suffix.is_float() => Constant::Float(n as f64),
+            (value, _) => value,
+        };
+
+        let fits = match (&value, suffix) {
+            (Constant::Integer(_), Some(suffix)) => !suffix.is_float(),
+            (Constant::Float(_), Some(suffix)) => suffix.is_float(),
+            (Constant::Boolean(_), _) => name == "bool",
+            (Constant::String(_), _) => name == "str",
+            (Constant::Char(_), _) => name == "char",
+            _ => false,
+        };
+        if !fits {
+            let kind = ErrorKind::ConstantType {
+                value: value.to_string(),
+                ty: constant.ty.to_string(),
+            };
+            return self.error(span, kind);
+        }
+
+        let (n, suffix) = match (&value, suffix) {
+            (Constant::Integer(n), Some(suffix)) => (*n, suffix),
+            _ => return Some(value),
+        };
+        if n.unsigned_abs() > u128::from(u64::MAX) {
+            return self.error(span, ErrorKind::ConstantOverflow);
+        }
+        let max = i128::from(suffix.max());
+        let min = if suffix.is_signed() { -max - 1 } else { 0 };
+        if n < min || n > max {
+            let kind = ErrorKind::ConstantRange {
+                value: n.to_string(),
+                ty: name.to_string(),
+            };
+            return self.error(span, kind);
+        }
+        Some(value)
+    }
+
+    fn error(&mut self, span: Span, kind: ErrorKind) -> Option<Constant> {
+        self.errors.push(Error { span, kind });
+        None
+    }
+}
+
+/// Applies `op` to two constants of the same type.
+fn binary(op: BinaryOp, lhs: Constant, rhs: Constant) -> Result<Constant, ErrorKind> {
+    use Constant::*;
+
+    if std::mem::discriminant(&lhs) != std::mem::discriminant(&rhs) {
+        return Err(ErrorKind::ConstantMismatch);
+    }
+    let ordering = lhs.partial_cmp(&rhs);
+    let compare = |accept: &[Ordering]| Ok(Boolean(ordering.is_some_and(|o| accept.contains(&o))));
+
+    match (op, lhs, rhs) {
+        (BinaryOp::Equals, _, _) => compare(&[Ordering::Equal]),
+        (BinaryOp::NotEqual, _, _) => Ok(Boolean(ordering != Some(Ordering::Equal))),
+        (BinaryOp::Less, _, _) => compare(&[Ordering::Less]),
+        (BinaryOp::Greater, _, _) => compare(&[Ordering::Greater]),
+        (BinaryOp::LessEqual, _, _) => compare(&[Ordering::Less, Ordering::Equal]),
+        (BinaryOp::GreaterEqual, _, _) => compare(&[Ordering::Greater, Ordering::Equal]),
+        (BinaryOp::And, Boolean(a), Boolean(b)) => Ok(Boolean(a && b)),
+        (BinaryOp::Or, Boolean(a), Boolean(b)) => Ok(Boolean(a || b)),
+        (BinaryOp::Divide, Integer(_), Integer(0)) => Err(ErrorKind::DivisionByZero),
+        (op, Integer(a), Integer(b)) => {
+            let result = match op {
+                BinaryOp::Add => a.checked_add(b),
+                BinaryOp::Subtract => a.checked_sub(b),
+                BinaryOp::Multiply => a.checked_mul(b),
+                BinaryOp::Divide => a.checked_div(b),
+                _ => return Err(ErrorKind::ConstantMismatch),
+            };
+            result.map(Integer).ok_or(ErrorKind::ConstantOverflow)
+        }
+        (op, Float(a), Float(b)) => match op {
+            BinaryOp::Add => Ok(Float(a + b)),
+            BinaryOp::Subtract => Ok(Float(a - b)),
+            BinaryOp::Multiply => Ok(Float(a * b)),
+            BinaryOp::Divide => Ok(Float(a / b)),
+            _ => Err(ErrorKind::ConstantMismatch),
+        },
+        _ => Err(ErrorKind::ConstantMismatch),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parse;
+
+    /// The values of the constants in `src`, or the errors.
+    fn evaluate(src: &str) -> Result<Vec<Expression>, Vec<String>> {
+        let ast = Ast::parse_source(src).unwrap();
+        match evaluate_constants(&ast) {
+            Ok(ast) => Ok(ast.constants().map(|c| c.value.clone()).collect()),
+            Err(errors) => Err(errors.iter().map(Error::to_string).collect()),
+        }
+    }
+
+    fn expression(src: &str) -> Expression {
+        Expression::parse_str(src).unwrap().1
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(
+            evaluate(
+                "const LIMIT: u64 = 92
+const TABLE: u8 = LIMIT / 2 + 1
+const MIN: i64 = -LIMIT * 2
+const HALF: f32 = 1.0 / 2.0
+const BIG: bool = LIMIT >= 90 & !(TABLE == 47)
+const NAME: str = \"fib\"
+const ONE: f32 = 1
+const DOUBLE: f64 = LIMIT * 2"
+            ),
+            Ok(vec![
+                expression("92u64"),
+                expression("47u8"),
+                expression("-184i64"),
+                expression("0.5f32"),
+                expression("false"),
+                expression("\"fib\""),
+                expression("1.0f32"),
+                expression("184.0f64"),
+            ])
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            evaluate(
+                "const A: u64 = B + 1
+const B: u64 = A
+const C: u64 = f(1)
+const D: u64 = E
+const F: u64 = 1 / (2 - 2)
+const G: u8 = 256
+const H: i8 = -129
+const I: u64 = 1 + true
+const J: u64 = 0 - 1
+const K: bool = 5
+const L: u64 = \"fib\"
+const M: char = 1.5
+const N: (u64, u64) = 1"
+            ),
+            Err(vec![
+                "the value of constant `A` depends on itself".to_string(),
+                "cannot be evaluated at compile time".to_string(),
+                "no constant named `E`".to_string(),
+                "division by zero in constant".to_string(),
+                "constant value `256` doesn't fit into `u8`".to_string(),
+                "constant value `-129` doesn't fit into `i8`".to_string(),
+                "mismatched types in constant".to_string(),
+                "constant value `-1` doesn't fit into `u64`".to_string(),
+                "constant value `5` is not of type `bool`".to_string(),
+                "constant value `\"fib\"` is not of type `u64`".to_string(),
+                "constant value `1.5` is not of type `char`".to_string(),
+                "constant value `1` is not of type `(u64, u64)`".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn sample() {
+        let ast = Ast::parse_source(include_str!("../../sample-code/constants.abc")).unwrap();
+        assert!(evaluate_constants(&ast).is_ok());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::parse::Span;
+use std::fmt;
+
+/// What is wrong with a program that parsed fine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Assignment to a name no `let` or parameter declares.
+    UndeclaredAssignment(String),
+    /// `break` or `continue`, named by the string, outside of any loop.
+    OutsideLoop(&'static str),
+    /// `break 'label` or `continue 'label` without an enclosing loop of that label.
+    UndeclaredLabel(String),
+    /// A variant pattern naming no variant of any enum.
+    UnknownVariant(String),
+    /// A variant pattern with the wrong number of fields.
+    VariantFields {
+        variant: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A `let` whose pattern doesn't match every value, like an enum variant.
+    RefutableLet,
+    /// A match that lets some values through, with what is not covered.
+    NonExhaustiveMatch(Vec<String>),
+    /// `max::<u64, u64>` for a function with one type parameter.
+    TypeArguments {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A call to a generic function that doesn't determine a type parameter.
+    CannotInfer { param: String, function: String },
+    /// Arguments that make a type parameter two different types.
+    ConflictingTypes {
+        param: String,
+        first: String,
+        second: String,
+    },
+    /// An `if` whose branches have different types, `then`'s coming first.
+    IfElseTypes { then: String, otherwise: String },
+    /// A generic function instantiating itself with ever larger types.
+    InstanceLimit(String),
+    /// Assignment to a `const`.
+    AssignToConstant(String),
+    /// A global read by the value of a global declared before it.
+    UninitialisedGlobal(String),
+    /// Something in a constant that can't be computed at compile time,
+    /// like a call.
+    NotConstant,
+    /// A name in a constant that isn't another constant.
+    UnknownConstant(String),
+    /// A constant whose value needs itself.
+    ConstantCycle(String),
+    /// Operands of different types, like `1 + true`.
+    ConstantMismatch,
+    /// A constant computation leaving the range of integers.
+    ConstantOverflow,
+    /// A constant computation dividing an integer by zero.
+    DivisionByZero,
+    /// A constant integer out of the range of its declared type.
+    ConstantRange { value: String, ty: String },
+    /// A constant value of another kind than its declared type, like a
+    /// string for a `u64`.
+    ConstantType { value: String, ty: String },
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::UndeclaredAssignment(name) => {
+                write!(f, "cannot assign to undeclared variable `{}`", name)
+            }
+            ErrorKind::OutsideLoop(keyword) => write!(f, "`{}` outside of a loop", keyword),
+            ErrorKind::UndeclaredLabel(label) => {
+                write!(f, "use of undeclared label `{}`", label)
+            }
+            ErrorKind::UnknownVariant(name) => write!(f, "no variant named `{}`", name),
+            ErrorKind::VariantFields {
+                variant,
+                expected,
+                found,
+            } => write!(
+                f,
+                "variant `{}` has {} field{}, but the pattern has {}",
+                variant,
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                found
+            ),
+            ErrorKind::RefutableLet => write!(f, "refutable pattern in `let`"),
+            ErrorKind::NonExhaustiveMatch(missing) => {
+                let missing: Vec<_> = missing.iter().map(|m| format!("`{}`", m)).collect();
+                write!(f, "non-exhaustive match, missing {}", missing.join(", "))
+            }
+            ErrorKind::TypeArguments {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{}` takes {} type argument{}, but {} {} given",
+                function,
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                found,
+                if *found == 1 { "was" } else { "were" }
+            ),
+            ErrorKind::CannotInfer { param, function } => {
+                write!(f, "cannot infer type `{}` of `{}`", param, function)
+            }
+            ErrorKind::ConflictingTypes {
+                param,
+                first,
+                second,
+            } => write!(
+                f,
+                "conflicting types for `{}`: `{}` and `{}`",
+                param, first, second
+            ),
+            ErrorKind::IfElseTypes { then, otherwise } => write!(
+                f,
+                "`if` and `else` have different types: `{}` and `{}`",
+                then, otherwise
+            ),
+            ErrorKind::AssignToConstant(name) => {
+                write!(f, "cannot assign to constant `{}`", name)
+            }
+            ErrorKind::UninitialisedGlobal(name) => {
+                write!(f, "global `{}` is used before it is initialised", name)
+            }
+            ErrorKind::NotConstant => write!(f, "cannot be evaluated at compile time"),
+            ErrorKind::UnknownConstant(name) => write!(f, "no constant named `{}`", name),
+            ErrorKind::ConstantCycle(name) => {
+                write!(f, "the value of constant `{}` depends on itself", name)
+            }
+            ErrorKind::ConstantMismatch => write!(f, "mismatched types in constant"),
+            ErrorKind::ConstantOverflow => write!(f, "overflow evaluating constant"),
+            ErrorKind::DivisionByZero => write!(f, "division by zero in constant"),
+            ErrorKind::ConstantRange { value, ty } => {
+                write!(f, "constant value `{}` doesn't fit into `{}`", value, ty)
+            }
+            ErrorKind::ConstantType { value, ty } => {
+                write!(f, "constant value `{}` is not of type `{}`", value, ty)
+            }
+            ErrorKind::InstanceLimit(function) => {
+                write!(
+                    f,
+                    "`{}` instantiates itself with ever larger types",
+                    function
+                )
+            }
+        }
+    }
+}
+
+/// A semantic error, located at the offending node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    pub span: Span,
+    pub kind: ErrorKind,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
@@ -0,0 +1,823 @@
+mod constant;
+mod error;
+mod mono;
+mod types;
+pub use constant::evaluate_constants;
+pub use error::{Error, ErrorKind};
+pub use mono::monomorphize;
+
+use crate::parse::{
+    Ast, Body, Enum, Expression, ExpressionKind, Function, Global, Ident, Item, MatchArm, Pattern,
+    PatternKind, Statement, StatementKind, Value, Variant,
+};
+
+/// Checks a parsed program, reporting every error found in source order.
+pub fn check(ast: &Ast) -> Vec<Error> {
+    let mut checker = Checker {
+        enums: ast.enums().collect(),
+        constants: ast.constants().map(|c| c.name.name.clone()).collect(),
+        globals: ast.globals().map(|g| g.name.name.clone()).collect(),
+        ..Checker::default()
+    };
+    let mut typer = types::Typer::new(ast);
+    for item in &ast.items {
+        match item {
+            Item::Function(function) => {
+                checker.function(function);
+                typer.function(function);
+            }
+            Item::Global(global) => {
+                checker.global(global);
+                typer.global(global);
+            }
+            _ => {}
+        }
+    }
+
+    let mut errors = checker.errors;
+    errors.append(&mut typer.errors);
+    errors.sort_by_key(|e| e.span.start);
+    errors
+}
+
+#[derive(Default)]
+struct Checker<'a> {
+    enums: Vec<&'a Enum>,
+    constants: Vec<String>,
+    /// The globals in the order they are initialised.
+    globals: Vec<String>,
+    /// How many globals are initialised at the current item.
+    initialised: usize,
+    /// The variables in scope, innermost block last.
+    scopes: Vec<Vec<String>>,
+    /// The labels of the loops around the current statement, innermost last.
+    loops: Vec<Option<String>>,
+    errors: Vec<Error>,
+}
+
+/// A column of a pattern matrix holds the pattern for the same part of the
+/// matched value in every row, `None` where any value matches.
+type Row<'p> = Vec<Option<&'p Pattern>>;
+
+impl<'a> Checker<'a> {
+    fn function(&mut self, function: &Function) {
+        let args = function.args.iter().map(|(name, _)| name.name.clone());
+        self.scopes = vec![self.globals.clone(), args.collect()];
+        self.loops.clear();
+        self.body(&function.body);
+    }
+
+    fn global(&mut self, global: &Global) {
+        self.scopes = vec![self.globals[..self.initialised].to_vec()];
+        self.loops.clear();
+        self.expression(&global.value);
+        self.uninitialised(&global.value);
+        self.initialised += 1;
+    }
+
+    /// Reports the globals an initialiser reads before they are set.
+    fn uninitialised(&mut self, expression: &Expression) {
+        if let ExpressionKind::Literal(literal) = &expression.kind {
+            let name = &literal.ident.name;
+            if self.globals[self.initialised..].contains(name) {
+                self.errors.push(Error {
+                    span: literal.ident.span,
+                    kind: ErrorKind::UninitialisedGlobal(name.clone()),
+                });
+            }
+        }
+        for child in expression.children() {
+            self.uninitialised(child);
+        }
+    }
+
+    fn body(&mut self, body: &Body) {
+        self.scopes.push(Vec::new());
+        for statement in body.statements() {
+            self.statement(statement);
+        }
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Let {
+                pattern, assign, ..
+            } => {
+                if let Some(assign) = assign {
+                    self.expression(assign);
+                }
+
+                let mut bindings = Vec::new();
+                self.pattern(pattern, &mut bindings);
+                if !self.missing(&[vec![Some(pattern)]], 1).is_empty() {
+                    self.errors.push(Error {
+                        span: pattern.span,
+                        kind: ErrorKind::RefutableLet,
+                    });
+                }
+                for name in bindings {
+                    self.declare(&name);
+                }
+            }
+            StatementKind::While {
+                label,
+                condition,
+                then,
+            } => {
+                self.expression(condition);
+                self.loop_body(label, Vec::new(), then);
+            }
+            StatementKind::For {
+                label,
+                variable,
+                start,
+                end,
+                then,
+                ..
+            } => {
+                self.expression(start);
+                self.expression(end);
+                self.loop_body(label, vec![variable.name.clone()], then);
+            }
+            StatementKind::Break(label) => self.jump("break", statement, label),
+            StatementKind::Continue(label) => self.jump("continue", statement, label),
+            StatementKind::Assign { target, value, .. } => {
+                match target.place_root() {
+                    Some(root) if !self.is_declared(&root.name) => {
+                        let kind = if self.constants.contains(&root.name) {
+                            ErrorKind::AssignToConstant(root.name.clone())
+                        } else {
+                            ErrorKind::UndeclaredAssignment(root.name.clone())
+                        };
+                        self.errors.push(Error {
+                            span: root.span,
+                            kind,
+                        });
+                    }
+                    _ => {}
+                }
+                self.expression(target);
+                self.expression(value);
+            }
+            StatementKind::Return(Some(e)) => self.expression(e),
+            StatementKind::Expression(e) => self.expression(e),
+            _ => {}
+        }
+    }
+
+    /// Checks the body of a loop, with `variables` declared in it.
+    fn loop_body(&mut self, label: &Option<Ident>, variables: Vec<String>, body: &Body) {
+        self.loops
+            .push(label.as_ref().map(|label| label.name.clone()));
+        self.scopes.push(variables);
+        self.body(body);
+        self.scopes.pop();
+        self.loops.pop();
+    }
+
+    /// Checks that a `break` or `continue` has a loop to leave.
+    fn jump(&mut self, keyword: &'static str, statement: &Statement, label: &Option<Ident>) {
+        let error = match label {
+            _ if self.loops.is_empty() => Error {
+                span: statement.span,
+                kind: ErrorKind::OutsideLoop(keyword),
+            },
+            Some(label) if !self.loops.contains(&Some(label.name.clone())) => Error {
+                span: label.span,
+                kind: ErrorKind::UndeclaredLabel(label.name.clone()),
+            },
+            _ => return,
+        };
+        self.errors.push(error);
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        for child in expression.children() {
+            self.expression(child);
+        }
+        match &expression.kind {
+            ExpressionKind::Match { arms, .. } => self.match_arms(expression, arms),
+            ExpressionKind::If {
+                then, otherwise, ..
+            } => {
+                self.body(then);
+                if let Some(otherwise) = otherwise {
+                    self.body(otherwise);
+                }
+            }
+            ExpressionKind::Block(body) => self.body(body),
+            ExpressionKind::Lambda { args, body, .. } => {
+                // The body sees the variables around the lambda, which it
+                // captures, but not the loops it could leave.
+                let loops = std::mem::take(&mut self.loops);
+                self.scopes
+                    .push(args.iter().map(|(name, _)| name.name.clone()).collect());
+                self.body(body);
+                self.scopes.pop();
+                self.loops = loops;
+            }
+            _ => {}
+        }
+    }
+
+    fn match_arms(&mut self, expression: &Expression, arms: &[MatchArm]) {
+        for arm in arms {
+            let mut bindings = Vec::new();
+            self.pattern(&arm.pattern, &mut bindings);
+
+            self.scopes.push(bindings);
+            self.body(&arm.body);
+            self.scopes.pop();
+        }
+
+        let rows: Vec<Row> = arms.iter().map(|arm| vec![Some(&arm.pattern)]).collect();
+        let missing: Vec<String> = self.missing(&rows, 1).into_iter().flatten().collect();
+        if missing.is_empty() {
+            return;
+        }
+        self.errors.push(Error {
+            span: expression.span,
+            kind: ErrorKind::NonExhaustiveMatch(missing),
+        });
+    }
+
+    /// Checks that `pattern` names existing variants and collects the
+    /// variables it binds.
+    fn pattern(&mut self, pattern: &Pattern, bindings: &mut Vec<String>) {
+        let (name, fields) = match &pattern.kind {
+            PatternKind::Name(name) => (name, None),
+            PatternKind::Variant { name, fields } => (name, Some(fields)),
+            PatternKind::Tuple(items) => {
+                for item in items {
+                    self.pattern(item, bindings);
+                }
+                return;
+            }
+            _ => return,
+        };
+        let found = fields.map_or(0, Vec::len);
+
+        match (self.variant(Some(pattern)), fields) {
+            (None, None) => bindings.push(name.name.clone()),
+            (None, Some(_)) => self.errors.push(Error {
+                span: name.span,
+                kind: ErrorKind::UnknownVariant(name.name.clone()),
+            }),
+            (Some((_, variant, _)), _) if variant.fields.len() != found => {
+                self.errors.push(Error {
+                    span: pattern.span,
+                    kind: ErrorKind::VariantFields {
+                        variant: name.name.clone(),
+                        expected: variant.fields.len(),
+                        found,
+                    },
+                })
+            }
+            _ => {}
+        }
+
+        for field in fields.into_iter().flatten() {
+            self.pattern(field, bindings);
+        }
+    }
+
+    /// The enum and variant `pattern` matches, with the patterns for its fields.
+    fn variant<'p>(
+        &self,
+        pattern: Option<&'p Pattern>,
+    ) -> Option<(&'a Enum, &'a Variant, &'p [Pattern])> {
+        let (name, fields) = match &pattern?.kind {
+            PatternKind::Name(name) => (name, &[][..]),
+            PatternKind::Variant { name, fields } => (name, &fields[..]),
+            _ => return None,
+        };
+        self.enums
+            .iter()
+            .find_map(|e| Some((*e, e.variant(&name.name)?, fields)))
+    }
+
+    /// The values no row matches, each written as one pattern per column.
+    /// Goes column by column, listing what is missing for every way to build
+    /// the value in the first column.
+    fn missing(&self, rows: &[Row], columns: usize) -> Vec<Vec<String>> {
+        if rows.is_empty() {
+            return vec![vec!["_".to_string(); columns]];
+        }
+        if columns == 0 {
+            return Vec::new();
+        }
+
+        let constructors = match self.constructors(rows) {
+            Some(constructors) => constructors,
+            // Literals never cover all values, so only the rows matching
+            // anything in this column are left.
+            None => {
+                let rows: Vec<Row> = rows
+                    .iter()
+                    .filter(|row| is_irrefutable(row[0]))
+                    .map(|row| row[1..].to_vec())
+                    .collect();
+                let missing = self.missing(&rows, columns - 1).into_iter();
+                return missing
+                    .map(|rest| std::iter::once("_".to_string()).chain(rest).collect())
+                    .collect();
+            }
+        };
+
+        let mut missing = Vec::new();
+        for constructor in constructors {
+            let arity = constructor.arity();
+            let rows = self.specialize(rows, &constructor);
+            for mut fields in self.missing(&rows, arity + columns - 1) {
+                let rest = fields.split_off(arity);
+                missing.push(
+                    std::iter::once(constructor.show(fields))
+                        .chain(rest)
+                        .collect(),
+                );
+            }
+        }
+        missing
+    }
+
+    /// Every way to build a value of the type matched in the first column,
+    /// `None` if there are too many to list, as for numbers.
+    fn constructors(&self, rows: &[Row]) -> Option<Vec<Constructor<'a>>> {
+        rows.iter().find_map(|row| {
+            if let Some((enum_, _, _)) = self.variant(row[0]) {
+                return Some(enum_.variants.iter().map(Constructor::Variant).collect());
+            }
+            match &row[0]?.kind {
+                PatternKind::Tuple(items) => Some(vec![Constructor::Tuple(items.len())]),
+                PatternKind::Value(Value::Boolean(_)) => {
+                    Some(vec![Constructor::Bool(true), Constructor::Bool(false)])
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// The rows that match values built with `constructor` in the first
+    /// column, with that column replaced by one for each of its fields.
+    fn specialize<'p>(&self, rows: &[Row<'p>], constructor: &Constructor) -> Vec<Row<'p>> {
+        let arity = constructor.arity();
+        let padded = |patterns: &'p [Pattern]| -> Row<'p> {
+            let patterns = patterns.iter().map(Some).chain(std::iter::repeat(None));
+            patterns.take(arity).collect()
+        };
+
+        rows.iter()
+            .filter_map(|row| {
+                let fields = match (self.variant(row[0]), constructor) {
+                    (Some((_, v, fields)), Constructor::Variant(variant)) => {
+                        if !std::ptr::eq(v, *variant) {
+                            return None;
+                        }
+                        padded(fields)
+                    }
+                    (Some(_), _) => return None,
+                    (None, _) => match (row[0].map(|p| &p.kind), constructor) {
+                        (Some(PatternKind::Tuple(items)), Constructor::Tuple(_)) => padded(items),
+                        (
+                            Some(PatternKind::Value(Value::Boolean(b))),
+                            Constructor::Bool(expected),
+                        ) if b == expected => Vec::new(),
+                        _ if is_irrefutable(row[0]) => vec![None; arity],
+                        _ => return None,
+                    },
+                };
+                Some(fields.into_iter().chain(row[1..].iter().copied()).collect())
+            })
+            .collect()
+    }
+
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(name.to_string());
+        }
+    }
+
+    fn is_declared(&self, name: &str) -> bool {
+        self.scopes.iter().flatten().any(|n| n == name)
+    }
+}
+
+/// One way to build a value, which a pattern may or may not match.
+enum Constructor<'a> {
+    Variant(&'a Variant),
+    Bool(bool),
+    /// A tuple of that many elements.
+    Tuple(usize),
+}
+
+impl Constructor<'_> {
+    fn arity(&self) -> usize {
+        match self {
+            Constructor::Variant(variant) => variant.fields.len(),
+            Constructor::Bool(_) => 0,
+            Constructor::Tuple(arity) => *arity,
+        }
+    }
+
+    /// How a value built with this is written, given patterns for its fields.
+    fn show(&self, fields: Vec<String>) -> String {
+        match self {
+            Constructor::Variant(variant) if fields.is_empty() => variant.name.name.clone(),
+            Constructor::Variant(variant) => {
+                format!("{}({})", variant.name.name, fields.join(", "))
+            }
+            Constructor::Bool(b) => b.to_string(),
+            Constructor::Tuple(1) => format!("({},)", fields[0]),
+            Constructor::Tuple(_) => format!("({})", fields.join(", ")),
+        }
+    }
+}
+
+/// Whether a pattern matches any value, given that it names no variant.
+fn is_irrefutable(pattern: Option<&Pattern>) -> bool {
+    match pattern {
+        None => true,
+        Some(pattern) => matches!(pattern.kind, PatternKind::Wildcard | PatternKind::Name(_)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::LineCol;
+
+    fn errors(src: &str) -> Vec<(String, LineCol)> {
+        let ast = Ast::parse_source(src).unwrap();
+        check(&ast)
+            .iter()
+            .map(|e| (e.to_string(), e.span.line_col(src)))
+            .collect()
+    }
+
+    #[test]
+    fn samples() {
+        assert_eq!(
+            errors(include_str!("../../sample-code/fibonacci.abc")),
+            vec![]
+        );
+        assert_eq!(
+            errors(include_str!("../../sample-code/fibonacci-iterative.abc")),
+            vec![]
+        );
+        assert_eq!(
+            errors(include_str!("../../sample-code/fibonacci-table.abc")),
+            vec![]
+        );
+        assert_eq!(errors(include_str!("../../sample-code/points.abc")), vec![]);
+        assert_eq!(errors(include_str!("../../sample-code/option.abc")), vec![]);
+        assert_eq!(errors(include_str!("../../sample-code/primes.abc")), vec![]);
+        assert_eq!(
+            errors(include_str!("../../sample-code/higher-order.abc")),
+            vec![]
+        );
+        assert_eq!(errors(include_str!("../../sample-code/divmod.abc")), vec![]);
+        assert_eq!(
+            errors(include_str!("../../sample-code/generics.abc")),
+            vec![]
+        );
+        assert_eq!(
+            errors(include_str!("../../sample-code/constants.abc")),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn globals() {
+        let src = "const LIMIT: u64 = 92
+global first: u64 = second + LIMIT
+global second: u64 = first + 1
+global third: fn() -> u64 = || second + fourth
+global fourth: u64 = 0
+
+function main() {
+    first += 1
+    LIMIT = 3
+    let LIMIT = 4
+    LIMIT = 5
+}";
+        assert_eq!(
+            errors(src),
+            vec![
+                (
+                    "global `second` is used before it is initialised".to_string(),
+                    LineCol {
+                        line: 2,
+                        column: 21
+                    }
+                ),
+                (
+                    "cannot assign to constant `LIMIT`".to_string(),
+                    LineCol { line: 9, column: 5 }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn branch_scopes() {
+        let src = "function main(n: u64) {
+    let a = if n > 1 { let b = n
+        b } else { { let c = 1
+        c = 2 } }
+    b = a
+    c = a
+}";
+        assert_eq!(
+            errors(src),
+            vec![
+                (
+                    "cannot assign to undeclared variable `b`".to_string(),
+                    LineCol { line: 5, column: 5 }
+                ),
+                (
+                    "cannot assign to undeclared variable `c`".to_string(),
+                    LineCol { line: 6, column: 5 }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn assignments() {
+        let src = "function count(n: u64) {
+    let i = 0
+    while i < n {
+        let j = i
+        i += 1
+        j = 2
+        n = n - 1
+        a[j] = 3
+    }
+    j = 3
+    k -= 1
+    q.x = 1
+}";
+        assert_eq!(
+            errors(src),
+            vec![
+                (
+                    "cannot assign to undeclared variable `a`".to_string(),
+                    LineCol { line: 8, column: 9 }
+                ),
+                (
+                    "cannot assign to undeclared variable `j`".to_string(),
+                    LineCol {
+                        line: 10,
+                        column: 5
+                    }
+                ),
+                (
+                    "cannot assign to undeclared variable `k`".to_string(),
+                    LineCol {
+                        line: 11,
+                        column: 5
+                    }
+                ),
+                (
+                    "cannot assign to undeclared variable `q`".to_string(),
+                    LineCol {
+                        line: 12,
+                        column: 5
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn loops() {
+        let src = "function main() {
+    break
+    'a: while true {
+        for i in 0..10 {
+            i = i + 1
+            continue 'a
+            break 'b
+        }
+    }
+    i = 0
+    if true { continue }
+}";
+        assert_eq!(
+            errors(src),
+            vec![
+                (
+                    "`break` outside of a loop".to_string(),
+                    LineCol { line: 2, column: 5 }
+                ),
+                (
+                    "use of undeclared label `'b`".to_string(),
+                    LineCol {
+                        line: 7,
+                        column: 19
+                    }
+                ),
+                (
+                    "cannot assign to undeclared variable `i`".to_string(),
+                    LineCol {
+                        line: 10,
+                        column: 5
+                    }
+                ),
+                (
+                    "`continue` outside of a loop".to_string(),
+                    LineCol {
+                        line: 11,
+                        column: 15
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn source_order() {
+        let src = "function main(n: u64) {
+    let a = if n > 1 { n } else { true }
+    break
+}";
+        assert_eq!(
+            errors(src),
+            vec![
+                (
+                    "`if` and `else` have different types: `u64` and `bool`".to_string(),
+                    LineCol {
+                        line: 2,
+                        column: 35
+                    }
+                ),
+                (
+                    "`break` outside of a loop".to_string(),
+                    LineCol { line: 3, column: 5 }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn lambdas() {
+        let src = "function main() {
+    let total = 0
+    while true {
+        let add = |x: u64| {
+            total += x
+            x = 1
+            y = 2
+            break
+        }
+    }
+}";
+        assert_eq!(
+            errors(src),
+            vec![
+                (
+                    "cannot assign to undeclared variable `y`".to_string(),
+                    LineCol {
+                        line: 7,
+                        column: 13
+                    }
+                ),
+                (
+                    "`break` outside of a loop".to_string(),
+                    LineCol {
+                        line: 8,
+                        column: 13
+                    }
+                ),
+            ]
+        );
+    }
+
+    fn messages(src: &str) -> Vec<String> {
+        errors(src)
+            .into_iter()
+            .map(|(message, _)| message)
+            .collect()
+    }
+
+    const ENUMS: &str = "
+enum Option { Some(u64), None }
+enum Shape { Circle(f64), Rect(f64, f64), Empty }
+enum Pair { Pair(Option, Option) }
+";
+
+    #[test]
+    fn exhaustive_matches() {
+        let src = format!(
+            "{}function main() {{
+    match o {{ Some(x) => x, None => 0 }}
+    match o {{ None => 0, _ => 1 }}
+    match o {{ other => 1 }}
+    match s {{ Circle(_) => 1, Rect(w, h) => 2, Empty => 3 }}
+    match p {{ Pair(Some(a), _) => 1, Pair(None, Some(b)) => 2, Pair(None, None) => 3 }}
+    match n {{ 0 => 1, n => n }}
+    match b {{ true => 1, false => 0 }}
+    match b {{ false => 0, _ => 1 }}
+}}",
+            ENUMS
+        );
+        assert_eq!(messages(&src), Vec::<String>::new());
+    }
+
+    #[test]
+    fn non_exhaustive_matches() {
+        let src = format!(
+            "{}function main() {{
+    match o {{ Some(x) => x }}
+    match s {{ Rect(a, b) => 1 }}
+    match o {{ Some(1) => 1, None => 0 }}
+    match n {{ 0 => 1, 1 => 1 }}
+    match b {{ true => 1 }}
+    match p {{ Pair(Some(a), _) => 1, Pair(_, None) => 2 }}
+}}",
+            ENUMS
+        );
+        assert_eq!(
+            errors(&src),
+            vec![
+                (
+                    "non-exhaustive match, missing `None`".to_string(),
+                    LineCol { line: 6, column: 5 }
+                ),
+                (
+                    "non-exhaustive match, missing `Circle(_)`, `Empty`".to_string(),
+                    LineCol { line: 7, column: 5 }
+                ),
+                (
+                    "non-exhaustive match, missing `Some(_)`".to_string(),
+                    LineCol { line: 8, column: 5 }
+                ),
+                (
+                    "non-exhaustive match, missing `_`".to_string(),
+                    LineCol { line: 9, column: 5 }
+                ),
+                (
+                    "non-exhaustive match, missing `false`".to_string(),
+                    LineCol {
+                        line: 10,
+                        column: 5
+                    }
+                ),
+                (
+                    "non-exhaustive match, missing `Pair(None, Some(_))`".to_string(),
+                    LineCol {
+                        line: 11,
+                        column: 5
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn tuples() {
+        let src = format!(
+            "{}function main() {{
+    let (q, (_, r)) = (1, (2, 3))
+    q = r
+    r.0 = 1
+    let None = o
+    match (q, r) {{ (0, _) => 1, (_, x) => 2 }}
+    match (o, r) {{ (Some(_), 0) => 1, (None, _) => 2 }}
+    match (b, c) {{ (true, true) => 1, (false, _) => 2 }}
+    match (b, o) {{ (true, _) => 1, (false, Some(x)) => 2, (false, None) => 3 }}
+}}",
+            ENUMS
+        );
+        assert_eq!(
+            messages(&src),
+            vec![
+                "refutable pattern in `let`",
+                "non-exhaustive match, missing `(Some(_), _)`",
+                "non-exhaustive match, missing `(true, false)`",
+            ]
+        );
+    }
+
+    #[test]
+    fn patterns() {
+        let src = format!(
+            "{}function main() {{
+    match o {{ Sum(x) => 1, _ => 0 }}
+    match o {{ Some => 1, Some(a, b) => 2, _ => 0 }}
+    match o {{ Some(x) => {{ x = 1 }}, None => {{ x = 2 }} }}
+}}",
+            ENUMS
+        );
+        assert_eq!(
+            messages(&src),
+            vec![
+                "no variant named `Sum`",
+                "variant `Some` has 1 field, but the pattern has 0",
+                "variant `Some` has 1 field, but the pattern has 2",
+                "cannot assign to undeclared variable `x`",
+            ]
+        );
+    }
+}
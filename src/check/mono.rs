@@ -0,0 +1,675 @@
+//! Replaces every generic function by one copy per list of type arguments
+//! it is used with, so that later stages only ever see concrete types.
+
+use super::types::{join, settle, unite, Typer};
+use super::{Error, ErrorKind};
+use crate::parse::{
+    Ast, Body, Expression, ExpressionKind, Function, Ident, Item, Literal, Span, Statement,
+    StatementKind, Type, TypeKind,
+};
+use std::collections::{HashMap, VecDeque};
+
+/// How large the type arguments of an instance may get, counted in the
+/// types they are made of. Only a generic function instantiating itself with
+/// ever larger types gets there.
+const SIZE_LIMIT: usize = 256;
+
+/// Type parameters with the types they stand for in an instance.
+pub(super) type Bindings = HashMap<String, Type>;
+
+/// Instantiates the generic functions of a program. Generic functions are
+/// left out of the result, their instances, named like `max<u64>`, come
+/// after the other items. Type arguments a call doesn't spell out are
+/// inferred from the types of its arguments, where integers and floats
+/// without suffix are `u64` and `f64` unless another argument says otherwise.
+pub fn monomorphize(ast: &Ast) -> Result<Ast, Vec<Error>> {
+    let mut mono = Mono::new(ast);
+    let mut items = Vec::new();
+
+    for item in &ast.items {
+        match item {
+            Item::Function(function) if !function.type_params.is_empty() => {}
+            Item::Function(function) => {
+                let mut function = function.clone();
+                mono.function(&mut function, Bindings::new());
+                items.push(Item::Function(function));
+            }
+            Item::Global(global) => {
+                let mut global = global.clone();
+                mono.types.start(&[]);
+                mono.expression(&mut global.value);
+                items.push(Item::Global(global));
+            }
+            item => items.push(item.clone()),
+        }
+    }
+    while let Some((mut function, bindings)) = mono.pending.pop_front() {
+        mono.function(&mut function, bindings);
+        items.push(Item::Function(function));
+    }
+
+    if mono.errors.is_empty() {
+        Ok(Ast {
+            items,
+            span: ast.span,
+        })
+    } else {
+        // Every instance of a generic function reports the errors in it.
+        let mut errors = mono.errors;
+        errors.sort_by_key(|e| e.span.start);
+        errors.dedup_by(|a, b| a.span.start == b.span.start && a.kind == b.kind);
+        Err(errors)
+    }
+}
+
+struct Mono<'a> {
+    /// The variables in scope and the types of expressions. The `if`s it
+    /// finds mismatched are left for `check` to report.
+    types: Typer<'a>,
+    /// Instances yet to be rewritten, with what their type parameters stand for.
+    pending: VecDeque<(Function, Bindings)>,
+    /// What the type parameters of the current function stand for.
+    bindings: Bindings,
+    errors: Vec<Error>,
+}
+
+impl<'a> Mono<'a> {
+    fn new(ast: &'a Ast) -> Mono<'a> {
+        Mono {
+            types: Typer::new(ast),
+            pending: VecDeque::new(),
+            bindings: Bindings::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn function(&mut self, function: &mut Function, bindings: Bindings) {
+        self.bindings = bindings;
+        self.types.start(&function.args);
+        self.body(&mut function.body);
+    }
+
+    fn body(&mut self, body: &mut Body) {
+        self.types.enter(&[]);
+        for statement in body.statements_mut() {
+            self.statement(statement);
+        }
+        self.types.leave();
+    }
+
+    fn statement(&mut self, statement: &mut Statement) {
+        match &mut statement.kind {
+            StatementKind::Let {
+                pattern,
+                ty,
+                assign,
+            } => {
+                if let Some(ty) = ty {
+                    *ty = substitute(ty, &self.bindings);
+                }
+                if let Some(assign) = assign {
+                    self.expression(assign);
+                }
+                let ty = match (ty, assign) {
+                    (Some(ty), _) => Some(ty.clone()),
+                    (None, Some(assign)) => self.types.expression(assign),
+                    (None, None) => None,
+                };
+                self.types.bind(pattern, ty);
+            }
+            StatementKind::While {
+                condition, then, ..
+            } => {
+                self.expression(condition);
+                self.body(then);
+            }
+            StatementKind::For {
+                variable,
+                start,
+                end,
+                then,
+                ..
+            } => {
+                self.expression(start);
+                self.expression(end);
+                let start = self.types.expression(start);
+                let ty = join(start, self.types.expression(end));
+                self.types.enter(&[]);
+                self.types.declare(variable, ty);
+                self.body(then);
+                self.types.leave();
+            }
+            StatementKind::Assign { target, value, .. } => {
+                self.expression(target);
+                self.expression(value);
+            }
+            StatementKind::Return(Some(e)) => self.expression(e),
+            StatementKind::Expression(e) => self.expression(e),
+            _ => {}
+        }
+    }
+
+    fn expression(&mut self, expression: &mut Expression) {
+        let span = expression.span;
+        match &mut expression.kind {
+            ExpressionKind::Literal(literal) => self.literal(literal, span),
+            ExpressionKind::Call { callee, arguments } => {
+                for argument in &mut arguments.items {
+                    self.expression(argument);
+                }
+                if let ExpressionKind::Literal(literal) = &mut callee.kind {
+                    if let Some(generic) = self.types.generic(&literal.ident.name) {
+                        if literal.type_arguments.is_empty() {
+                            let items = arguments.items.iter();
+                            let types: Vec<_> = items.map(|a| self.types.expression(a)).collect();
+                            match self.infer(generic, &types, span) {
+                                Some(types) => literal.type_arguments = types,
+                                None => return,
+                            }
+                        }
+                    }
+                }
+                self.expression(callee);
+            }
+            ExpressionKind::Match { scrutinee, arms } => {
+                self.expression(scrutinee);
+                let ty = self.types.expression(scrutinee);
+                for arm in arms {
+                    self.types.enter(&[]);
+                    self.types.bind(&arm.pattern, ty.clone());
+                    self.body(&mut arm.body);
+                    self.types.leave();
+                }
+            }
+            ExpressionKind::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                self.expression(condition);
+                self.body(then);
+                if let Some(otherwise) = otherwise {
+                    self.body(otherwise);
+                }
+            }
+            ExpressionKind::Block(body) => self.body(body),
+            ExpressionKind::Lambda {
+                args,
+                return_type,
+                body,
+            } => {
+                for (_, ty) in args.iter_mut() {
+                    *ty = substitute(ty, &self.bindings);
+                }
+                if let Some(ty) = return_type {
+                    *ty = substitute(ty, &self.bindings);
+                }
+                self.types.enter(args);
+                self.body(body);
+                self.types.leave();
+            }
+            _ => {
+                for child in expression.children_mut() {
+                    self.expression(child);
+                }
+            }
+        }
+    }
+
+    /// Points a reference to a generic function at the instance for its
+    /// type arguments.
+    fn literal(&mut self, literal: &mut Literal, span: Span) {
+        for ty in &mut literal.type_arguments {
+            *ty = substitute(ty, &self.bindings);
+        }
+        let name = &literal.ident.name;
+        let found = literal.type_arguments.len();
+
+        let generic = match self.types.generic(name) {
+            Some(generic) => generic,
+            None => {
+                let known = self.types.functions.contains_key(name);
+                if found > 0 && (known || self.types.lookup(name).is_some()) {
+                    self.errors.push(Error {
+                        span,
+                        kind: ErrorKind::TypeArguments {
+                            function: name.clone(),
+                            expected: 0,
+                            found,
+                        },
+                    });
+                }
+                return;
+            }
+        };
+
+        let expected = generic.type_params.len();
+        if found == 0 {
+            self.errors.push(Error {
+                span,
+                kind: ErrorKind::CannotInfer {
+                    param: generic.type_params[0].name.clone(),
+                    function: name.clone(),
+                },
+            });
+        } else if found != expected {
+            self.errors.push(Error {
+                span,
+                kind: ErrorKind::TypeArguments {
+                    function: name.clone(),
+                    expected,
+                    found,
+                },
+            });
+        } else if let Some(instance) =
+            self.instantiate(generic, std::mem::take(&mut literal.type_arguments), span)
+        {
+            literal.ident.name = instance;
+        }
+    }
+
+    /// The type arguments of a call to `generic` with arguments of the given
+    /// types, if they determine all of them.
+    fn infer(
+        &mut self,
+        generic: &Function,
+        arguments: &[Option<Type>],
+        span: Span,
+    ) -> Option<Vec<Type>> {
+        let mut bindings = Bindings::new();
+        for ((_, param), argument) in generic.args.iter().zip(arguments) {
+            let argument = match argument {
+                Some(argument) => argument,
+                None => continue,
+            };
+            if let Err(kind) = unify(param, argument, &generic.type_params, &mut bindings) {
+                self.errors.push(Error { span, kind });
+                return None;
+            }
+        }
+
+        let mut types = Vec::new();
+        for param in &generic.type_params {
+            match bindings.remove(&param.name) {
+                Some(ty) => types.push(settle(&ty)),
+                None => {
+                    self.errors.push(Error {
+                        span,
+                        kind: ErrorKind::CannotInfer {
+                            param: param.name.clone(),
+                            function: generic.name.name.clone(),
+                        },
+                    });
+                    return None;
+                }
+            }
+        }
+        Some(types)
+    }
+
+    /// The name of the instance of `generic` for `types`, queueing it to be
+    /// rewritten if it is new.
+    fn instantiate(
+        &mut self,
+        generic: &'a Function,
+        types: Vec<Type>,
+        span: Span,
+    ) -> Option<String> {
+        if types.iter().map(size).sum::<usize>() > SIZE_LIMIT {
+            self.errors.push(Error {
+                span,
+                kind: ErrorKind::InstanceLimit(generic.name.name.clone()),
+            });
+            return None;
+        }
+
+        let list: Vec<_> = types.iter().map(Type::to_string).collect();
+        let name = format!("{}<{}>", generic.name.name, list.join(", "));
+        if self.types.functions.contains_key(&name) {
+            return Some(name);
+        }
+
+        let params = generic.type_params.iter().map(|p| p.name.clone());
+        let bindings: Bindings = params.zip(types).collect();
+        let mut function = generic.clone();
+        function.name.name = name.clone();
+        function.type_params.clear();
+        for (_, ty) in &mut function.args {
+            *ty = substitute(ty, &bindings);
+        }
+        if let Some(ty) = &mut function.return_type {
+            *ty = substitute(ty, &bindings);
+        }
+
+        self.types
+            .functions
+            .insert(name.clone(), function_type(&function));
+        self.pending.push_back((function, bindings));
+        Some(name)
+    }
+}
+
+/// `fn(args) -> return_type`
+pub(super) fn function_type(function: &Function) -> Type {
+    TypeKind::Function {
+        params: function.args.iter().map(|(_, ty)| ty.clone()).collect(),
+        result: function.return_type.clone().map(Box::new),
+    }
+    .into()
+}
+
+/// How many types `ty` is made of, itself included.
+fn size(ty: &Type) -> usize {
+    let list = |types: &[Type]| types.iter().map(size).sum::<usize>();
+    1 + match &ty.kind {
+        TypeKind::Named(_) => 0,
+        TypeKind::Generic { arguments, .. } => list(arguments),
+        TypeKind::Array { element, .. } | TypeKind::Reference(element) => size(element),
+        TypeKind::Tuple(types) => list(types),
+        TypeKind::Function { params, result } => {
+            list(params) + result.as_ref().map_or(0, |r| size(r))
+        }
+    }
+}
+
+/// Replaces the type parameters in `ty` by what they stand for.
+pub(super) fn substitute(ty: &Type, bindings: &Bindings) -> Type {
+    let list = |types: &[Type]| types.iter().map(|ty| substitute(ty, bindings)).collect();
+    let kind = match &ty.kind {
+        TypeKind::Named(name) => match bindings.get(name) {
+            Some(bound) => bound.kind.clone(),
+            None => TypeKind::Named(name.clone()),
+        },
+        TypeKind::Generic { name, arguments } => TypeKind::Generic {
+            name: name.clone(),
+            arguments: list(arguments),
+        },
+        TypeKind::Array { element, len } => TypeKind::Array {
+            element: Box::new(substitute(element, bindings)),
+            len: *len,
+        },
+        TypeKind::Tuple(types) => TypeKind::Tuple(list(types)),
+        TypeKind::Function { params, result } => TypeKind::Function {
+            params: list(params),
+            result: result.as_ref().map(|r| Box::new(substitute(r, bindings))),
+        },
+        TypeKind::Reference(target) => TypeKind::Reference(Box::new(substitute(target, bindings))),
+    };
+    Type {
+        kind,
+        span: ty.span,
+    }
+}
+
+/// Binds the type parameters in `param` so that it becomes `argument`,
+/// failing if one would need to be two different types. An integer or float
+/// without suffix only binds a parameter until an argument of a type it fits
+/// does. Parts that don't line up are left to the type checker.
+pub(super) fn unify(
+    param: &Type,
+    argument: &Type,
+    params: &[Ident],
+    bindings: &mut Bindings,
+) -> Result<(), ErrorKind> {
+    let all = |a: &[Type], b: &[Type], bindings: &mut Bindings| {
+        if a.len() == b.len() {
+            for (param, argument) in a.iter().zip(b) {
+                unify(param, argument, params, bindings)?;
+            }
+        }
+        Ok(())
+    };
+
+    match (&param.kind, &argument.kind) {
+        (TypeKind::Named(name), _) if params.iter().any(|p| p.name == *name) => {
+            let ty = match bindings.get(name) {
+                Some(bound) => {
+                    unite(bound, argument).ok_or_else(|| ErrorKind::ConflictingTypes {
+                        param: name.clone(),
+                        first: bound.to_string(),
+                        second: argument.to_string(),
+                    })?
+                }
+                None => argument.clone(),
+            };
+            bindings.insert(name.clone(), ty);
+            Ok(())
+        }
+        (
+            TypeKind::Generic {
+                name: a,
+                arguments: x,
+            },
+            TypeKind::Generic {
+                name: b,
+                arguments: y,
+            },
+        ) if a == b => all(x, y, bindings),
+        (TypeKind::Array { element: a, .. }, TypeKind::Array { element: b, .. })
+        | (TypeKind::Reference(a), TypeKind::Reference(b)) => unify(a, b, params, bindings),
+        (TypeKind::Tuple(a), TypeKind::Tuple(b)) => all(a, b, bindings),
+        (
+            TypeKind::Function {
+                params: a,
+                result: r,
+            },
+            TypeKind::Function {
+                params: b,
+                result: s,
+            },
+        ) => {
+            all(a, b, bindings)?;
+            match (r, s) {
+                (Some(r), Some(s)) => unify(r, s, params, bindings),
+                _ => Ok(()),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The names of the functions after monomorphization, or the errors.
+    fn functions(src: &str) -> Result<Vec<String>, Vec<String>> {
+        let ast = Ast::parse_source(src).unwrap();
+        match monomorphize(&ast) {
+            Ok(ast) => Ok(ast.functions().map(|f| f.name.name.clone()).collect()),
+            Err(errors) => Err(errors.iter().map(Error::to_string).collect()),
+        }
+    }
+
+    const MAX: &str = "
+function max<T>(a: T, b: T) -> T {
+    if a > b { return a }
+    return b
+}
+";
+
+    #[test]
+    fn explicit() {
+        let src = format!(
+            "{}function main() {{ max::<u8>(1, 2) max::<u8>(3, 4) }}",
+            MAX
+        );
+        assert_eq!(functions(&src), Ok(vec!["main".into(), "max<u8>".into()]));
+    }
+
+    #[test]
+    fn inferred() {
+        let src = format!(
+            "{}
+function inc(x: u64) -> u64 {{ return x + 1 }}
+function apply<A, B>(f: fn(A) -> B, x: A) -> B {{ return f(x) }}
+function first<T>(items: [T]) -> T {{ return items[0] }}
+function swap<A, B>(pair: (A, B)) -> (B, A) {{ return (pair.1, pair.0) }}
+function main() {{
+    let x = 2.5
+    max(x, 1.0)
+    apply(inc, 1)
+    first([1i8, 2i8])
+    swap(('a', true))
+}}",
+            MAX
+        );
+        assert_eq!(
+            functions(&src),
+            Ok(vec![
+                "inc".into(),
+                "main".into(),
+                "max<f64>".into(),
+                "apply<u64, u64>".into(),
+                "first<i8>".into(),
+                "swap<char, bool>".into(),
+            ])
+        );
+    }
+
+    #[test]
+    fn unsuffixed() {
+        let src = format!(
+            "{}
+function pair<A, B>(a: A, b: B) -> (A, B) {{ return (a, b) }}
+function main() {{
+    max(1, 2u8)
+    let n = 3
+    max(n, 4i32)
+    max(0.5, 1f32)
+    pair(1, 2.5)
+    pair((n, 1), [1.5, 2f32])
+}}",
+            MAX
+        );
+        assert_eq!(
+            functions(&src),
+            Ok(vec![
+                "main".into(),
+                "max<u8>".into(),
+                "max<i32>".into(),
+                "max<f32>".into(),
+                "pair<u64, f64>".into(),
+                "pair<(u64, u64), [f32; 2]>".into(),
+            ])
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let src = format!(
+            "{}
+function pair<A, B>(a: A, b: B) -> (A, B) {{ return (a, b) }}
+function twice<T>(x: T) -> (T, T) {{ return pair(x, x) }}
+function main() {{
+    let t = twice(1u8)
+    max(t.0, 2u8)
+}}",
+            MAX
+        );
+        assert_eq!(
+            functions(&src),
+            Ok(vec![
+                "main".into(),
+                "twice<u8>".into(),
+                "max<u8>".into(),
+                "pair<u8, u8>".into(),
+            ])
+        );
+    }
+
+    #[test]
+    fn globals() {
+        let src = format!(
+            "{}
+const LIMIT: u8 = 92
+global largest: u8 = max(LIMIT, 7u8)
+function main() {{ max(largest, 1u8) }}",
+            MAX
+        );
+        assert_eq!(functions(&src), Ok(vec!["main".into(), "max<u8>".into()]));
+    }
+
+    #[test]
+    fn branches() {
+        let src = format!(
+            "{}
+enum Option {{ Some(u8), None }}
+function main(n: u8) {{
+    let small = if n < 2 {{ n }} else {{ 2u8 }}
+    max(small, {{ let m = 1u8
+        m }})
+    let big = {{ if n > 2 {{ return 0 }} else {{ 1.5 }} }}
+    max(big, 2.5)
+    match Some(n) {{ Some(x) => max(x, 1), None => 0u8 }}
+    let f = |x: i16| x
+    max(f(1), 2)
+}}",
+            MAX
+        );
+        assert_eq!(
+            functions(&src),
+            Ok(vec![
+                "main".into(),
+                "max<u8>".into(),
+                "max<f64>".into(),
+                "max<i16>".into(),
+            ])
+        );
+    }
+
+    #[test]
+    fn shadowed() {
+        let src = format!(
+            "{}function main() {{ let max = |a: u64, b: u64| a max(1, 2) }}",
+            MAX
+        );
+        assert_eq!(functions(&src), Ok(vec!["main".into()]));
+    }
+
+    #[test]
+    fn errors() {
+        let src = format!(
+            "{}
+function none<T>() -> u64 {{ return 0 }}
+function inc(x: u64) -> u64 {{ return x + 1 }}
+function main() {{
+    max::<u64, u64>(1, 2)
+    none()
+    max(1, 2.0)
+    inc::<u64>(1)
+    let f = max
+}}",
+            MAX
+        );
+        assert_eq!(
+            functions(&src),
+            Err(vec![
+                "`max` takes 1 type argument, but 2 were given".into(),
+                "cannot infer type `T` of `none`".into(),
+                "conflicting types for `T`: `{integer}` and `{float}`".into(),
+                "`inc` takes 0 type arguments, but 1 was given".into(),
+                "cannot infer type `T` of `max`".into(),
+            ])
+        );
+    }
+
+    #[test]
+    fn instance_limit() {
+        let src = "function grow<T>(x: T) { grow((x, x)) }
+function main() { grow(1) }";
+        assert_eq!(
+            functions(src),
+            Err(vec![
+                "`grow` instantiates itself with ever larger types".into()
+            ])
+        );
+    }
+
+    #[test]
+    fn sample() {
+        let ast = Ast::parse_source(include_str!("../../sample-code/generics.abc")).unwrap();
+        let ast = monomorphize(&ast).unwrap();
+        assert!(ast.functions().all(|f| f.type_params.is_empty()));
+    }
+}
@@ -0,0 +1,620 @@
+//! Works out the types of expressions from the types written in the program,
+//! to check that both branches of an `if` have the same type, and for
+//! monomorphization to infer type arguments from the types of arguments.
+//!
+//! The types of literals, parameters, constants, globals and functions are
+//! known, as are those of variables declared with a type or with a value of
+//! known type. So are the types of what is built from these with operators,
+//! calls, fields, indexes and tuple elements, of lambdas, and of the
+//! variables a variant pattern binds. Blocks, `if`s and matches have the type
+//! of their value, a match that of its arms.
+//!
+//! A call to a generic function has a known type if its type arguments are
+//! given or follow from the types of the arguments. Integers and floats
+//! without a suffix fit any integer or float type.
+//!
+//! An `if` is only checked if the types of both branches are known. The type
+//! of a branch isn't known if it ends with something other than an
+//! expression, like `return`, or if it uses a variable declared without type
+//! or value.
+
+use super::mono::{function_type, substitute, unify, Bindings};
+use super::{Error, ErrorKind};
+use crate::parse::{
+    Ast, BinaryOp, Body, Enum, Expression, ExpressionKind, Function, Global, Ident, Pattern,
+    PatternKind, Statement, StatementKind, Struct, Suffix, Type, TypeKind, UnaryOp, Value,
+};
+use std::collections::HashMap;
+
+/// The type of an integer without suffix, fitting any integer type.
+pub(super) const INTEGER: &str = "{integer}";
+/// The type of a float without suffix, fitting any float type.
+pub(super) const FLOAT: &str = "{float}";
+
+pub(super) struct Typer<'a> {
+    generics: HashMap<&'a str, &'a Function>,
+    /// The type of every concrete function, including the instances
+    /// monomorphization adds.
+    pub(super) functions: HashMap<String, Type>,
+    structs: HashMap<&'a str, &'a Struct>,
+    /// The enum of every variant, by the variant's name.
+    variants: HashMap<&'a str, &'a Enum>,
+    /// The types of constants and globals.
+    globals: HashMap<&'a str, &'a Type>,
+    /// The variables in scope with their types, as far as they are known,
+    /// innermost block last.
+    scopes: Vec<HashMap<String, Option<Type>>>,
+    /// The `if`s whose branches have different types.
+    pub(super) errors: Vec<Error>,
+}
+
+impl<'a> Typer<'a> {
+    pub(super) fn new(ast: &'a Ast) -> Typer<'a> {
+        let (generics, functions): (Vec<_>, Vec<_>) =
+            ast.functions().partition(|f| !f.type_params.is_empty());
+
+        Typer {
+            generics: generics
+                .into_iter()
+                .map(|f| (f.name.name.as_str(), f))
+                .collect(),
+            functions: functions
+                .into_iter()
+                .map(|f| (f.name.name.clone(), function_type(f)))
+                .collect(),
+            structs: ast.structs().map(|s| (s.name.name.as_str(), s)).collect(),
+            variants: ast
+                .enums()
+                .flat_map(|e| e.variants.iter().map(move |v| (v.name.name.as_str(), e)))
+                .collect(),
+            globals: ast
+                .constants()
+                .map(|c| (c.name.name.as_str(), &c.ty))
+                .chain(ast.globals().map(|g| (g.name.name.as_str(), &g.ty)))
+                .collect(),
+            scopes: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub(super) fn function(&mut self, function: &Function) {
+        self.start(&function.args);
+        self.body(&function.body);
+    }
+
+    pub(super) fn global(&mut self, global: &Global) {
+        self.start(&[]);
+        self.expression(&global.value);
+    }
+
+    /// Forgets all variables but the parameters `args` of a new function.
+    pub(super) fn start(&mut self, args: &[(Ident, Type)]) {
+        self.scopes.clear();
+        self.enter(args);
+    }
+
+    /// Opens a scope declaring the parameters `args`.
+    pub(super) fn enter(&mut self, args: &[(Ident, Type)]) {
+        let args = args.iter();
+        self.scopes.push(
+            args.map(|(name, ty)| (name.name.clone(), Some(ty.clone())))
+                .collect(),
+        );
+    }
+
+    /// Closes the innermost scope.
+    pub(super) fn leave(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Declares the variable `name` in the innermost scope.
+    pub(super) fn declare(&mut self, name: &Ident, ty: Option<Type>) {
+        let scope = self.scopes.last_mut().expect("a scope to declare in");
+        scope.insert(name.name.clone(), ty);
+    }
+
+    /// Checks a body, returning the type of its value.
+    fn body(&mut self, body: &Body) -> Option<Type> {
+        self.enter(&[]);
+        let mut ty = None;
+        for statement in body.statements() {
+            ty = self.statement(statement);
+        }
+        self.leave();
+        ty
+    }
+
+    /// Checks a statement, returning its type if it is an expression.
+    fn statement(&mut self, statement: &Statement) -> Option<Type> {
+        match &statement.kind {
+            StatementKind::Let {
+                pattern,
+                ty,
+                assign,
+            } => {
+                let found = assign.as_ref().and_then(|assign| self.expression(assign));
+                self.bind(pattern, ty.clone().or(found));
+            }
+            StatementKind::While {
+                condition, then, ..
+            } => {
+                self.expression(condition);
+                self.body(then);
+            }
+            StatementKind::For {
+                variable,
+                start,
+                end,
+                then,
+                ..
+            } => {
+                let start = self.expression(start);
+                let ty = join(start, self.expression(end));
+                self.enter(&[]);
+                self.declare(variable, ty);
+                self.body(then);
+                self.leave();
+            }
+            StatementKind::Assign { target, value, .. } => {
+                self.expression(target);
+                self.expression(value);
+            }
+            StatementKind::Return(Some(e)) => {
+                self.expression(e);
+            }
+            StatementKind::Expression(e) => return self.expression(e),
+            _ => {}
+        }
+        None
+    }
+
+    /// Checks an expression and everything in it, returning its type.
+    pub(super) fn expression(&mut self, expression: &Expression) -> Option<Type> {
+        match &expression.kind {
+            ExpressionKind::Value(value) => Some(Type::named(match value {
+                Value::Boolean(_) => "bool",
+                Value::Integer(_, suffix) => suffix.map_or(INTEGER, |s| s.name()),
+                Value::Float(_, suffix) => suffix.map_or(FLOAT, |s| s.name()),
+                Value::String(_) => "str",
+                Value::Char(_) => "char",
+            })),
+            ExpressionKind::Literal(literal) => {
+                let name = literal.ident.name.as_str();
+                if let Some(ty) = self.lookup(name) {
+                    return ty;
+                }
+                if let Some(ty) = self.functions.get(name) {
+                    return Some(ty.clone()).filter(|_| literal.type_arguments.is_empty());
+                }
+                if let Some(generic) = self.generics.get(name) {
+                    if literal.type_arguments.len() != generic.type_params.len() {
+                        return None;
+                    }
+                    let params = generic.type_params.iter().map(|p| p.name.clone());
+                    let bindings = params.zip(literal.type_arguments.clone()).collect();
+                    return Some(substitute(&function_type(generic), &bindings));
+                }
+                if let Some(ty) = self.globals.get(name) {
+                    return Some((*ty).clone());
+                }
+                let enumeration = self.variants.get(name)?;
+                let ty = Type::named(enumeration.name.name.clone());
+                match enumeration.variant(name) {
+                    Some(variant) if !variant.fields.is_empty() => Some(
+                        TypeKind::Function {
+                            params: variant.fields.clone(),
+                            result: Some(Box::new(ty)),
+                        }
+                        .into(),
+                    ),
+                    _ => Some(ty),
+                }
+            }
+            ExpressionKind::Binary { op, lhs, rhs } => {
+                let lhs = self.expression(lhs);
+                let rhs = self.expression(rhs);
+                match op {
+                    BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide => {
+                        join(lhs, rhs)
+                    }
+                    _ => Some(Type::named("bool")),
+                }
+            }
+            ExpressionKind::Unary { op, operand } => {
+                let operand = self.expression(operand);
+                match op {
+                    UnaryOp::Not => Some(Type::named("bool")),
+                    UnaryOp::Negative => operand,
+                }
+            }
+            ExpressionKind::Array(items) => {
+                let types: Vec<_> = items.iter().map(|item| self.expression(item)).collect();
+                let element = types.into_iter().fold(None, join)?;
+                Some(
+                    TypeKind::Array {
+                        element: Box::new(element),
+                        len: Some(items.len() as u64),
+                    }
+                    .into(),
+                )
+            }
+            ExpressionKind::Tuple(items) => {
+                let types: Vec<_> = items.iter().map(|item| self.expression(item)).collect();
+                Some(TypeKind::Tuple(types.into_iter().collect::<Option<_>>()?).into())
+            }
+            ExpressionKind::Index { target, index } => {
+                let target = self.expression(target);
+                self.expression(index);
+                match target?.kind {
+                    TypeKind::Array { element, .. } => Some(*element),
+                    _ => None,
+                }
+            }
+            ExpressionKind::Struct { name, fields } => {
+                for (_, value) in fields {
+                    self.expression(value);
+                }
+                Some(Type::named(name.name.clone()))
+            }
+            ExpressionKind::Field { target, field } => match self.expression(target)?.kind {
+                TypeKind::Named(name) => {
+                    let fields = &self.structs.get(name.as_str())?.fields;
+                    let (_, ty) = fields.iter().find(|(name, _)| name.name == field.name)?;
+                    Some(ty.clone())
+                }
+                _ => None,
+            },
+            ExpressionKind::Element { target, index } => match self.expression(target)?.kind {
+                TypeKind::Tuple(mut types) if *index < types.len() => {
+                    Some(types.swap_remove(*index))
+                }
+                _ => None,
+            },
+            ExpressionKind::Call { callee, arguments } => {
+                let arguments: Vec<_> = arguments
+                    .items
+                    .iter()
+                    .map(|argument| self.expression(argument))
+                    .collect();
+                let callee = match &callee.kind {
+                    ExpressionKind::Literal(literal) if literal.type_arguments.is_empty() => {
+                        match self.generic(&literal.ident.name) {
+                            Some(generic) => instance(generic, &arguments),
+                            None => self.expression(callee),
+                        }
+                    }
+                    _ => self.expression(callee),
+                };
+                match callee?.kind {
+                    TypeKind::Function { result, .. } => Some(result.map_or_else(unit, |r| *r)),
+                    _ => None,
+                }
+            }
+            ExpressionKind::Match { scrutinee, arms } => {
+                let scrutinee = self.expression(scrutinee);
+                let mut ty = None;
+                for arm in arms {
+                    self.enter(&[]);
+                    self.bind(&arm.pattern, scrutinee.clone());
+                    ty = join(ty, self.body(&arm.body));
+                    self.leave();
+                }
+                ty
+            }
+            ExpressionKind::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                self.expression(condition);
+                let then = self.body(then);
+                let otherwise = match otherwise {
+                    Some(otherwise) => (otherwise, self.body(otherwise)),
+                    None => return Some(unit()),
+                };
+                match (then, otherwise) {
+                    (Some(then), (body, Some(ty))) => match unite(&then, &ty) {
+                        Some(ty) => Some(ty),
+                        None => {
+                            self.errors.push(Error {
+                                span: body.value().map_or(body.span, |value| value.span),
+                                kind: ErrorKind::IfElseTypes {
+                                    then: then.to_string(),
+                                    otherwise: ty.to_string(),
+                                },
+                            });
+                            None
+                        }
+                    },
+                    (then, (_, otherwise)) => then.or(otherwise),
+                }
+            }
+            ExpressionKind::Block(body) => self.body(body),
+            ExpressionKind::Lambda {
+                args,
+                return_type,
+                body,
+            } => {
+                self.enter(args);
+                let value = self.body(body);
+                self.leave();
+
+                let result = match (return_type, value) {
+                    (Some(ty), _) => Some(ty.clone()),
+                    (None, Some(ty)) if ty == unit() => None,
+                    (None, Some(ty)) => Some(ty),
+                    (None, None) => return None,
+                };
+                Some(
+                    TypeKind::Function {
+                        params: args.iter().map(|(_, ty)| ty.clone()).collect(),
+                        result: result.map(Box::new),
+                    }
+                    .into(),
+                )
+            }
+        }
+    }
+
+    /// The generic function `name` refers to, unless a variable shadows it.
+    pub(super) fn generic(&self, name: &str) -> Option<&'a Function> {
+        match self.lookup(name) {
+            Some(_) => None,
+            None => self.generics.get(name).copied(),
+        }
+    }
+
+    /// The type of the variable `name`, `Some(None)` if it is in scope but
+    /// its type isn't known.
+    pub(super) fn lookup(&self, name: &str) -> Option<Option<Type>> {
+        let mut scopes = self.scopes.iter().rev();
+        scopes.find_map(|scope| scope.get(name)).cloned()
+    }
+
+    /// Declares the variables `pattern` binds in the innermost scope.
+    pub(super) fn bind(&mut self, pattern: &Pattern, ty: Option<Type>) {
+        match &pattern.kind {
+            PatternKind::Name(name) if !self.variants.contains_key(name.name.as_str()) => {
+                self.declare(name, ty)
+            }
+            PatternKind::Tuple(patterns) => {
+                for (i, pattern) in patterns.iter().enumerate() {
+                    let ty = match ty.as_ref().map(|ty| &ty.kind) {
+                        Some(TypeKind::Tuple(types)) if types.len() == patterns.len() => {
+                            Some(types[i].clone())
+                        }
+                        _ => None,
+                    };
+                    self.bind(pattern, ty);
+                }
+            }
+            PatternKind::Variant { name, fields } => {
+                let variant = self
+                    .variants
+                    .get(name.name.as_str())
+                    .and_then(|e| e.variant(&name.name));
+                for (i, field) in fields.iter().enumerate() {
+                    let ty = variant.and_then(|v| v.fields.get(i)).cloned();
+                    self.bind(field, ty);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The type of the instance of `generic` for arguments of the given types,
+/// if they determine all its type arguments.
+fn instance(generic: &Function, arguments: &[Option<Type>]) -> Option<Type> {
+    let mut bindings = Bindings::new();
+    for ((_, param), argument) in generic.args.iter().zip(arguments) {
+        if let Some(argument) = argument {
+            unify(param, argument, &generic.type_params, &mut bindings).ok()?;
+        }
+    }
+    if generic
+        .type_params
+        .iter()
+        .any(|p| !bindings.contains_key(&p.name))
+    {
+        return None;
+    }
+    Some(substitute(&function_type(generic), &bindings))
+}
+
+/// `()`, the type of no value at all.
+fn unit() -> Type {
+    TypeKind::Tuple(Vec::new()).into()
+}
+
+/// The type of a value that is of type `a` or `b`, as far as known. If they
+/// don't agree it is said to be `a`, leaving that mistake to a type checker.
+pub(super) fn join(a: Option<Type>, b: Option<Type>) -> Option<Type> {
+    match (a, b) {
+        (Some(a), Some(b)) => unite(&a, &b).or(Some(a)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The type that is both `a` and `b`, if they agree. An integer or float
+/// without suffix agrees with any type of its kind, an array of unknown
+/// length with one of any length.
+pub(super) fn unite(a: &Type, b: &Type) -> Option<Type> {
+    let list = |a: &[Type], b: &[Type]| -> Option<Vec<Type>> {
+        if a.len() != b.len() {
+            return None;
+        }
+        a.iter().zip(b).map(|(a, b)| unite(a, b)).collect()
+    };
+    let kind = match (&a.kind, &b.kind) {
+        (TypeKind::Named(x), TypeKind::Named(y)) if x == y => return Some(a.clone()),
+        (TypeKind::Named(x), _) if fits(x, b) => return Some(b.clone()),
+        (_, TypeKind::Named(y)) if fits(y, a) => return Some(a.clone()),
+        (
+            TypeKind::Generic { name, arguments },
+            TypeKind::Generic {
+                name: other,
+                arguments: others,
+            },
+        ) if name == other => TypeKind::Generic {
+            name: name.clone(),
+            arguments: list(arguments, others)?,
+        },
+        (
+            TypeKind::Array { element, len },
+            TypeKind::Array {
+                element: other,
+                len: other_len,
+            },
+        ) => {
+            let len = match (len, other_len) {
+                (Some(a), Some(b)) if a != b => return None,
+                (Some(_), Some(_)) => *len,
+                _ => None,
+            };
+            TypeKind::Array {
+                element: Box::new(unite(element, other)?),
+                len,
+            }
+        }
+        (TypeKind::Tuple(a), TypeKind::Tuple(b)) => TypeKind::Tuple(list(a, b)?),
+        (
+            TypeKind::Function { params, result },
+            TypeKind::Function {
+                params: other,
+                result: other_result,
+            },
+        ) => TypeKind::Function {
+            params: list(params, other)?,
+            result: match (result, other_result) {
+                (Some(a), Some(b)) => Some(Box::new(unite(a, b)?)),
+                (None, None) => None,
+                _ => return None,
+            },
+        },
+        (TypeKind::Reference(a), TypeKind::Reference(b)) => {
+            TypeKind::Reference(Box::new(unite(a, b)?))
+        }
+        _ => return None,
+    };
+    Some(Type { kind, span: a.span })
+}
+
+/// `ty` with integers and floats without suffix made `u64` and `f64`, the
+/// types they have when nothing else tells.
+pub(super) fn settle(ty: &Type) -> Type {
+    let defaults = [(INTEGER, "u64"), (FLOAT, "f64")].iter();
+    let bindings = defaults
+        .map(|(name, ty)| (name.to_string(), Type::named(*ty)))
+        .collect();
+    substitute(ty, &bindings)
+}
+
+/// Whether a number of type `name`, `{integer}` or `{float}`, fits `ty`.
+fn fits(name: &str, ty: &Type) -> bool {
+    let suffix = match &ty.kind {
+        TypeKind::Named(other) => Suffix::from_name(other),
+        _ => None,
+    };
+    match name {
+        INTEGER => suffix.is_some_and(|s| !s.is_float()),
+        FLOAT => suffix.is_some_and(Suffix::is_float),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::check::check;
+    use crate::parse::{Ast, LineCol};
+
+    fn errors(src: &str) -> Vec<(String, LineCol)> {
+        let ast = Ast::parse_source(src).unwrap();
+        check(&ast)
+            .iter()
+            .map(|e| (e.to_string(), e.span.line_col(src)))
+            .collect()
+    }
+
+    fn messages(src: &str) -> Vec<String> {
+        errors(src)
+            .into_iter()
+            .map(|(message, _)| message)
+            .collect()
+    }
+
+    #[test]
+    fn branches_agree() {
+        let src = "enum Option { Some(u8), None }
+struct Point { x: f32, y: f32 }
+global table: [u64] = [0, 1]
+function max<T>(a: T, b: T) -> T { if a > b { a } else { b } }
+function main(n: u8, p: Point) {
+    let a = if n < 2 { n } else { 2 }
+    let b = if n < 2 { 1.5 } else { p.x }
+    let c = if n < 2 { table } else { [1, 2] }
+    let d = if n < 2 { max(n, 1u8) } else { max::<u8>(1, 2) }
+    let e = if n < 2 { Some(n) } else { None }
+    let f = if n < 2 { return } else { true }
+    let g = if n < 2 { 0 } else if n < 4 { 1 } else { n }
+    let h = if n < 2 { |x: u8| x } else { |y: u8| y + 1 }
+    let i
+    let j = if n < 2 { i } else { true }
+}";
+        assert_eq!(messages(src), Vec::<String>::new());
+    }
+
+    #[test]
+    fn branches_disagree() {
+        let src = "enum Option { Some(u8), None }
+function main(n: u8) {
+    let a = if n < 2 { n } else { true }
+    let b = if n < 2 { let x = true
+        x } else { n }
+    let c = if n < 2 { match Some(n) { Some(x) => x, None => 0 } } else { \"none\" }
+    let d = if n < 2 { 1 } else if n < 4 { 2.0 } else { 3 }
+    let e = if n < 2 { (n, 1) } else { (n, 'x') }
+}";
+        assert_eq!(
+            errors(src),
+            vec![
+                (
+                    "`if` and `else` have different types: `u8` and `bool`".to_string(),
+                    LineCol {
+                        line: 3,
+                        column: 35
+                    }
+                ),
+                (
+                    "`if` and `else` have different types: `bool` and `u8`".to_string(),
+                    LineCol {
+                        line: 5,
+                        column: 20
+                    }
+                ),
+                (
+                    "`if` and `else` have different types: `u8` and `str`".to_string(),
+                    LineCol {
+                        line: 6,
+                        column: 75
+                    }
+                ),
+                (
+                    "`if` and `else` have different types: `{float}` and `{integer}`".to_string(),
+                    LineCol {
+                        line: 7,
+                        column: 57
+                    }
+                ),
+                (
+                    "`if` and `else` have different types: `(u8, {integer})` and `(u8, char)`"
+                        .to_string(),
+                    LineCol {
+                        line: 8,
+                        column: 40
+                    }
+                ),
+            ]
+        );
+    }
+}
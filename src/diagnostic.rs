@@ -0,0 +1,106 @@
+use crate::parse::{LineCol, Span};
+use std::fmt;
+
+/// An error as shown to the user: which file, where in it, and what went wrong.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub location: LineCol,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(file: &str, src: &str, span: Span, message: impl ToString) -> Diagnostic {
+        Diagnostic {
+            file: file.to_string(),
+            location: span.line_col(src),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// The files of a program, laid out one after another so that a span tells
+/// the file as well as the place in it.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+#[derive(Clone, Debug)]
+struct SourceFile {
+    name: String,
+    src: String,
+    start: usize,
+}
+
+impl SourceMap {
+    /// Adds a file after the others, returning the offset its spans start at.
+    pub fn add(&mut self, name: impl Into<String>, src: impl Into<String>) -> usize {
+        // The end of input of one file doesn't touch the next one.
+        let start = self.files.last().map_or(0, |f| f.start + f.src.len() + 1);
+        self.files.push(SourceFile {
+            name: name.into(),
+            src: src.into(),
+            start,
+        });
+        start
+    }
+
+    /// A diagnostic located in whichever file `span` falls into.
+    pub fn diagnostic(&self, span: Span, message: impl ToString) -> Diagnostic {
+        let file = self
+            .files
+            .iter()
+            .rev()
+            .find(|file| file.start <= span.start)
+            .expect("spans to lie in an added file");
+        let span = Span::new(span.start - file.start, span.end - file.start);
+        Diagnostic::new(&file.name, &file.src, span, message)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: error: {}",
+            self.file, self.location, self.message
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Ast;
+
+    #[test]
+    fn parse_error() {
+        let src = "function main() {\n    let x = (1 + 2\n}";
+        let error = &Ast::parse_source(src).unwrap_err()[0];
+
+        assert_eq!(
+            Diagnostic::new("main.abc", src, error.span, error).to_string(),
+            "main.abc:3:1: error: expected `)`, found `}`"
+        );
+    }
+
+    #[test]
+    fn source_map() {
+        let mut sources = SourceMap::default();
+        let main = sources.add("main.abc", "import \"math.abc\"\nfunction main() {");
+        let math = sources.add("math.abc", "function gcd(a: u64 {}");
+
+        let error = &Ast::parse_source_at("function gcd(a: u64 {}", math).unwrap_err()[0];
+        assert_eq!(
+            sources.diagnostic(error.span, error).to_string(),
+            "math.abc:1:21: error: expected `)`, found `{`"
+        );
+        assert_eq!(
+            sources
+                .diagnostic(Span::new(main + 18, main + 26), "here")
+                .to_string(),
+            "main.abc:2:1: error: here"
+        );
+    }
+}
@@ -0,0 +1,4 @@
+pub mod check;
+pub mod diagnostic;
+pub mod load;
+pub mod parse;
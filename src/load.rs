@@ -0,0 +1,576 @@
+//! Loads a program spread over several files. Every imported file is a
+//! module named after the file, whose functions the other files call as
+//! `math::gcd`. Structs, enums, constants and globals are shared by all files.
+
+use crate::diagnostic::{Diagnostic, SourceMap};
+use crate::parse::{
+    Ast, Body, Expression, ExpressionKind, Ident, Item, Pattern, PatternKind, Span, Statement,
+    StatementKind,
+};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// A program with the functions of all its files in one tree, named by
+/// their module, and the files to locate errors in it.
+pub struct Program {
+    pub ast: Ast,
+    pub sources: SourceMap,
+}
+
+/// What is wrong with the imports of a program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ErrorKind {
+    /// An imported file that can't be read, with why.
+    Read { path: String, reason: String },
+    /// Files importing each other, in the order they do.
+    Cycle(Vec<String>),
+    /// Two different files with the same module name.
+    DuplicateModule { name: String, path: String },
+    /// `name::f` without an import of `name`.
+    UnknownModule(String),
+    /// `math::f` or `use math::f` where `math` has no function `f`.
+    UnknownFunction { module: String, name: String },
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::Read { path, reason } => write!(f, "cannot read `{}`: {}", path, reason),
+            ErrorKind::Cycle(files) => write!(f, "import cycle: {}", files.join(" -> ")),
+            ErrorKind::DuplicateModule { name, path } => {
+                write!(f, "module `{}` is already loaded from `{}`", name, path)
+            }
+            ErrorKind::UnknownModule(name) => write!(f, "use of unimported module `{}`", name),
+            ErrorKind::UnknownFunction { module, name } => {
+                write!(f, "module `{}` has no function `{}`", module, name)
+            }
+        }
+    }
+}
+
+/// Loads the program starting at the file `path` with the source `src`,
+/// reading the files it imports from disk.
+pub fn load(path: &Path, src: String) -> Result<Program, Vec<Diagnostic>> {
+    load_with(path, src, |path| std::fs::read_to_string(path))
+}
+
+fn load_with(
+    path: &Path,
+    src: String,
+    read: impl Fn(&Path) -> io::Result<String>,
+) -> Result<Program, Vec<Diagnostic>> {
+    let mut loader = Loader {
+        read: &read,
+        sources: SourceMap::default(),
+        modules: Vec::new(),
+        loaded: HashSet::new(),
+        names: HashMap::new(),
+        stack: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    loader.file(normalize(path), src, None);
+    if !loader.diagnostics.is_empty() {
+        return Err(loader.diagnostics);
+    }
+
+    let functions: HashMap<_, HashSet<_>> = loader
+        .modules
+        .iter()
+        .filter_map(|(module, ast)| {
+            let names = ast.functions().map(|f| f.name.name.clone());
+            Some((module.name.clone()?, names.collect()))
+        })
+        .collect();
+
+    let root_span = loader.modules.last().expect("the root module").1.span;
+    let mut items = Vec::new();
+    for (module, mut ast) in std::mem::take(&mut loader.modules) {
+        let mut resolver = Resolver {
+            module: &module,
+            functions: &functions,
+            own: ast.functions().map(|f| f.name.name.clone()).collect(),
+            scopes: Vec::new(),
+            errors: Vec::new(),
+        };
+        for item in &mut ast.items {
+            resolver.item(item);
+        }
+        for (span, kind) in resolver.errors {
+            let diagnostic = loader.sources.diagnostic(span, kind);
+            loader.diagnostics.push(diagnostic);
+        }
+        items.extend(
+            ast.items
+                .into_iter()
+                .filter(|item| !matches!(item, Item::Import(_) | Item::Use(_))),
+        );
+    }
+
+    if loader.diagnostics.is_empty() {
+        Ok(Program {
+            ast: Ast {
+                items,
+                span: root_span,
+            },
+            sources: loader.sources,
+        })
+    } else {
+        Err(loader.diagnostics)
+    }
+}
+
+/// A loaded file, as far as name resolution is concerned.
+struct Module {
+    /// `None` for the file the program starts with, whose functions keep
+    /// their names.
+    name: Option<String>,
+    /// The modules the file imports, by name.
+    imports: HashSet<String>,
+    /// The functions `use`d by the file, with the module of each.
+    uses: HashMap<String, String>,
+}
+
+struct Loader<'r> {
+    read: &'r dyn Fn(&Path) -> io::Result<String>,
+    sources: SourceMap,
+    /// Every file loaded, imported files before the files importing them.
+    modules: Vec<(Module, Ast)>,
+    loaded: HashSet<PathBuf>,
+    /// The file of every module, by name.
+    names: HashMap<String, PathBuf>,
+    /// The files being loaded, each importing the next.
+    stack: Vec<PathBuf>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Loader<'_> {
+    fn file(&mut self, path: PathBuf, src: String, name: Option<String>) {
+        let start = self.sources.add(path.display().to_string(), src.as_str());
+        let ast = match Ast::parse_source_at(&src, start) {
+            Ok(ast) => ast,
+            Err(errors) => {
+                for e in errors {
+                    let diagnostic = self.sources.diagnostic(e.span, &e);
+                    self.diagnostics.push(diagnostic);
+                }
+                return;
+            }
+        };
+
+        let mut module = Module {
+            name,
+            imports: HashSet::new(),
+            uses: HashMap::new(),
+        };
+        self.stack.push(path.clone());
+        for item in &ast.items {
+            let (file, span) = match item {
+                Item::Import(import) => (PathBuf::from(&import.path), import.span),
+                Item::Use(u) => {
+                    let module_name = u.module.name.clone();
+                    module.uses.insert(u.name.name.clone(), module_name);
+                    (PathBuf::from(format!("{}.abc", u.module.name)), u.span)
+                }
+                _ => continue,
+            };
+            let dir = path.parent().unwrap_or_else(|| Path::new(""));
+            let target = normalize(&dir.join(file));
+            let name = module_name(&target);
+            module.imports.insert(name.clone());
+            self.import(target, name, span);
+        }
+        self.stack.pop();
+
+        self.loaded.insert(path);
+        self.modules.push((module, ast));
+    }
+
+    fn import(&mut self, path: PathBuf, name: String, span: Span) {
+        if let Some(i) = self.stack.iter().position(|p| *p == path) {
+            let cycle = self.stack[i..].iter().chain(Some(&path));
+            let cycle = cycle.map(|p| p.display().to_string()).collect();
+            self.error(span, ErrorKind::Cycle(cycle));
+            return;
+        }
+        if self.loaded.contains(&path) {
+            return;
+        }
+        if let Some(other) = self.names.get(&name) {
+            let kind = ErrorKind::DuplicateModule {
+                name,
+                path: other.display().to_string(),
+            };
+            self.error(span, kind);
+            return;
+        }
+
+        match (self.read)(&path) {
+            Ok(src) => {
+                self.names.insert(name.clone(), path.clone());
+                self.file(path, src, Some(name));
+            }
+            Err(e) => {
+                let kind = ErrorKind::Read {
+                    path: path.display().to_string(),
+                    reason: e.to_string(),
+                };
+                self.error(span, kind);
+            }
+        }
+    }
+
+    fn error(&mut self, span: Span, kind: ErrorKind) {
+        let diagnostic = self.sources.diagnostic(span, kind);
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+/// Gives the functions of a module their full names and points the names
+/// in it at the functions they refer to.
+struct Resolver<'a> {
+    module: &'a Module,
+    /// The functions of every module, by module name.
+    functions: &'a HashMap<String, HashSet<String>>,
+    /// The functions of this module.
+    own: HashSet<String>,
+    /// The variables in scope, innermost block last.
+    scopes: Vec<Vec<String>>,
+    errors: Vec<(Span, ErrorKind)>,
+}
+
+impl Resolver<'_> {
+    fn item(&mut self, item: &mut Item) {
+        match item {
+            Item::Function(function) => {
+                if let Some(module) = &self.module.name {
+                    function.name.name = format!("{}::{}", module, function.name.name);
+                }
+                let args = function.args.iter();
+                self.scopes = vec![args.map(|(name, _)| name.name.clone()).collect()];
+                self.body(&mut function.body);
+            }
+            Item::Global(global) => {
+                self.scopes = vec![Vec::new()];
+                self.expression(&mut global.value);
+            }
+            Item::Use(u) => {
+                let functions = &self.functions[&u.module.name];
+                if !functions.contains(&u.name.name) {
+                    let kind = ErrorKind::UnknownFunction {
+                        module: u.module.name.clone(),
+                        name: u.name.name.clone(),
+                    };
+                    self.errors.push((u.name.span, kind));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn body(&mut self, body: &mut Body) {
+        self.scopes.push(Vec::new());
+        for statement in body.statements_mut() {
+            self.statement(statement);
+        }
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, statement: &mut Statement) {
+        match &mut statement.kind {
+            StatementKind::Let {
+                pattern, assign, ..
+            } => {
+                if let Some(assign) = assign {
+                    self.expression(assign);
+                }
+                self.bind(pattern);
+            }
+            StatementKind::While {
+                condition, then, ..
+            } => {
+                self.expression(condition);
+                self.body(then);
+            }
+            StatementKind::For {
+                variable,
+                start,
+                end,
+                then,
+                ..
+            } => {
+                self.expression(start);
+                self.expression(end);
+                self.scopes.push(vec![variable.name.clone()]);
+                self.body(then);
+                self.scopes.pop();
+            }
+            StatementKind::Assign { target, value, .. } => {
+                self.expression(target);
+                self.expression(value);
+            }
+            StatementKind::Return(Some(e)) => self.expression(e),
+            StatementKind::Expression(e) => self.expression(e),
+            _ => {}
+        }
+    }
+
+    fn expression(&mut self, expression: &mut Expression) {
+        match &mut expression.kind {
+            ExpressionKind::Literal(literal) => self.name(&mut literal.ident),
+            ExpressionKind::Match { scrutinee, arms } => {
+                self.expression(scrutinee);
+                for arm in arms {
+                    self.scopes.push(Vec::new());
+                    self.bind(&arm.pattern);
+                    self.body(&mut arm.body);
+                    self.scopes.pop();
+                }
+            }
+            ExpressionKind::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                self.expression(condition);
+                self.body(then);
+                if let Some(otherwise) = otherwise {
+                    self.body(otherwise);
+                }
+            }
+            ExpressionKind::Block(body) => self.body(body),
+            ExpressionKind::Lambda { args, body, .. } => {
+                self.scopes
+                    .push(args.iter().map(|(name, _)| name.name.clone()).collect());
+                self.body(body);
+                self.scopes.pop();
+            }
+            _ => {
+                for child in expression.children_mut() {
+                    self.expression(child);
+                }
+            }
+        }
+    }
+
+    /// Rewrites a name referring to a function to the function's full name.
+    fn name(&mut self, ident: &mut Ident) {
+        if self.scopes.iter().any(|scope| scope.contains(&ident.name)) {
+            return;
+        }
+
+        if let Some((module, name)) = ident.name.split_once("::") {
+            let kind = match self.functions.get(module) {
+                Some(functions) if self.module.imports.contains(module) => {
+                    if functions.contains(name) {
+                        return;
+                    }
+                    ErrorKind::UnknownFunction {
+                        module: module.to_string(),
+                        name: name.to_string(),
+                    }
+                }
+                _ => ErrorKind::UnknownModule(module.to_string()),
+            };
+            self.errors.push((ident.span, kind));
+        } else if self.own.contains(&ident.name) {
+            if let Some(module) = &self.module.name {
+                ident.name = format!("{}::{}", module, ident.name);
+            }
+        } else if let Some(module) = self.module.uses.get(&ident.name) {
+            ident.name = format!("{}::{}", module, ident.name);
+        }
+    }
+
+    /// Declares the variables `pattern` binds.
+    fn bind(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Name(name) => {
+                let scope = self.scopes.last_mut().expect("a scope to bind in");
+                scope.push(name.name.clone());
+            }
+            PatternKind::Variant { fields, .. } | PatternKind::Tuple(fields) => {
+                for field in fields {
+                    self.bind(field);
+                }
+            }
+            PatternKind::Wildcard | PatternKind::Value(_) => {}
+        }
+    }
+}
+
+/// The name of the module in the file `path`, `math` for `lib/math.abc`.
+fn module_name(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default();
+    stem.to_string_lossy().into_owned()
+}
+
+/// Removes `.` and resolvable `..` from `path`, so that every file has
+/// one path no matter how it is imported.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(normal.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                normal.pop();
+            }
+            component => normal.push(component),
+        }
+    }
+    normal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loads the first of `files`, reading the others from memory.
+    fn load_files(files: &[(&str, &str)]) -> Result<Program, Vec<String>> {
+        let read = |path: &Path| match files.iter().find(|(name, _)| Path::new(name) == path) {
+            Some((_, src)) => Ok(src.to_string()),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "not found")),
+        };
+        let (path, src) = files[0];
+        load_with(Path::new(path), src.to_string(), read)
+            .map_err(|diagnostics| diagnostics.iter().map(Diagnostic::to_string).collect())
+    }
+
+    fn functions(program: &Program) -> Vec<&str> {
+        let functions = program.ast.functions();
+        functions.map(|f| f.name.name.as_str()).collect()
+    }
+
+    #[test]
+    fn modules() {
+        let program = load_files(&[
+            (
+                "main.abc",
+                "import \"lib/math.abc\"\nuse util::twice\nfunction main() { twice(math::lcm(4, 6)) }",
+            ),
+            (
+                "lib/math.abc",
+                "import \"../util.abc\"
+function gcd(a: u64, b: u64) -> u64 { return util::twice(a) }
+function lcm(a: u64, b: u64) -> u64 { return a / gcd(a, b) * b }
+function shadowed(gcd: fn(u64, u64) -> u64) -> u64 { return gcd(1, 2) }",
+            ),
+            ("util.abc", "function twice(x: u64) -> u64 { return x * 2 }"),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            functions(&program),
+            vec![
+                "util::twice",
+                "math::gcd",
+                "math::lcm",
+                "math::shadowed",
+                "main"
+            ]
+        );
+        let body = |name| {
+            let mut functions = program.ast.functions();
+            format!(
+                "{:?}",
+                functions.find(|f| f.name.name == name).unwrap().body
+            )
+        };
+        assert!(body("main").contains("\"util::twice\""));
+        assert!(body("math::lcm").contains("\"math::gcd\""));
+        assert!(!body("math::shadowed").contains("\"math::gcd\""));
+    }
+
+    #[test]
+    fn shared_imports() {
+        let program = load_files(&[
+            (
+                "main.abc",
+                "import \"a.abc\"\nimport \"./b.abc\"\nfunction main() {}",
+            ),
+            ("a.abc", "import \"c.abc\"\nfunction a() { c::c() }"),
+            ("b.abc", "import \"c.abc\"\nfunction b() { c::c() }"),
+            ("c.abc", "function c() {}"),
+        ])
+        .unwrap();
+
+        assert_eq!(functions(&program), vec!["c::c", "a::a", "b::b", "main"]);
+    }
+
+    #[test]
+    fn errors_name_the_file() {
+        assert_eq!(
+            load_files(&[
+                ("main.abc", "import \"a.abc\"\nfunction main() {}"),
+                ("a.abc", "import \"b.abc\""),
+                ("b.abc", "\n\nimport \"a.abc\""),
+            ])
+            .err(),
+            Some(vec![
+                "b.abc:3:1: error: import cycle: a.abc -> b.abc -> a.abc".to_string()
+            ])
+        );
+        assert_eq!(
+            load_files(&[
+                ("main.abc", "import \"math.abc\""),
+                ("math.abc", "function gcd(a: u64 {}"),
+            ])
+            .err(),
+            Some(vec![
+                "math.abc:1:21: error: expected `)`, found `{`".to_string()
+            ])
+        );
+        assert_eq!(
+            load_files(&[("src/main.abc", "use math::gcd")]).err(),
+            Some(vec![
+                "src/main.abc:1:1: error: cannot read `src/math.abc`: not found".to_string()
+            ])
+        );
+        assert_eq!(
+            load_files(&[
+                ("main.abc", "import \"math.abc\"\nimport \"lib/math.abc\""),
+                ("math.abc", ""),
+                ("lib/math.abc", ""),
+            ])
+            .err(),
+            Some(vec![
+                "main.abc:2:1: error: module `math` is already loaded from `math.abc`".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn unknown_names() {
+        assert_eq!(
+            load_files(&[
+                (
+                    "main.abc",
+                    "import \"a.abc\"
+use a::nope
+function main() { a::f() b::g() a::h() }",
+                ),
+                ("a.abc", "import \"b.abc\"\nfunction f() {}"),
+                ("b.abc", "function g() {}"),
+            ])
+            .err(),
+            Some(vec![
+                "main.abc:2:8: error: module `a` has no function `nope`".to_string(),
+                "main.abc:3:26: error: use of unimported module `b`".to_string(),
+                "main.abc:3:33: error: module `a` has no function `h`".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn sample() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("sample-code/modules.abc");
+        let src = std::fs::read_to_string(&path).unwrap();
+        let program = load(&path, src).unwrap();
+
+        assert!(functions(&program).contains(&"math::gcd"));
+        assert!(crate::check::check(&program.ast).is_empty());
+    }
+}
@@ -1,5 +1,73 @@
-mod parse;
+use learn_compiler::check;
+use learn_compiler::load;
+use std::path::Path;
+use std::process::exit;
 
 fn main() {
-    println!("Hello, world!");
+    let mut dump_ast = false;
+    let mut file = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--dump-ast" => dump_ast = true,
+            _ if file.is_none() && !arg.starts_with("--") => file = Some(arg),
+            _ => usage(),
+        }
+    }
+    let file = file.unwrap_or_else(|| usage());
+
+    let src = match std::fs::read_to_string(&file) {
+        Ok(src) => src,
+        Err(e) => {
+            eprintln!("{}: {}", file, e);
+            exit(2);
+        }
+    };
+
+    let program = match load::load(Path::new(&file), src) {
+        Ok(program) => program,
+        Err(diagnostics) => {
+            for d in diagnostics {
+                eprintln!("{}", d);
+            }
+            exit(1);
+        }
+    };
+    let sources = &program.sources;
+
+    let errors = check::check(&program.ast);
+    if !errors.is_empty() {
+        for e in errors {
+            eprintln!("{}", sources.diagnostic(e.span, &e));
+        }
+        exit(1);
+    }
+
+    let ast = match check::evaluate_constants(&program.ast) {
+        Ok(ast) => ast,
+        Err(errors) => {
+            for e in errors {
+                eprintln!("{}", sources.diagnostic(e.span, &e));
+            }
+            exit(1);
+        }
+    };
+
+    let ast = match check::monomorphize(&ast) {
+        Ok(ast) => ast,
+        Err(errors) => {
+            for e in errors {
+                eprintln!("{}", sources.diagnostic(e.span, &e));
+            }
+            exit(1);
+        }
+    };
+
+    if dump_ast {
+        println!("{:#?}", ast);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: learn-compiler [--dump-ast] <file>");
+    exit(2);
 }
@@ -0,0 +1,214 @@
+use crate::parse::{Input, LineCol, Span, TokenKind};
+use nom::error::{ErrorKind, ParseError};
+use std::fmt;
+
+/// What the parser was looking for when it failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expected {
+    Token(TokenKind),
+    Type,
+    Expression,
+    Statement,
+    /// Something that can be assigned to, like `x` or `a[i]`.
+    Place,
+    Pattern,
+    /// A plain decimal number after `.`, as in `pair.0`.
+    TupleIndex,
+    /// A valid escape sequence in a string or char literal.
+    Escape,
+    /// Exactly one character in a char literal.
+    Char,
+    /// A digit in the given radix.
+    Digit(u32),
+    /// A suffix like `u8` after a number.
+    Suffix,
+    /// A number that fits into the named type.
+    InRange(&'static str),
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expected::Token(kind) => write!(f, "{}", kind),
+            Expected::Type => write!(f, "type"),
+            Expected::Expression => write!(f, "expression"),
+            Expected::Statement => write!(f, "statement"),
+            Expected::Place => write!(f, "assignable expression"),
+            Expected::Pattern => write!(f, "pattern"),
+            Expected::TupleIndex => write!(f, "tuple index"),
+            Expected::Escape => write!(f, "escape sequence"),
+            Expected::Char => write!(f, "single character"),
+            Expected::Digit(2) => write!(f, "binary digit"),
+            Expected::Digit(8) => write!(f, "octal digit"),
+            Expected::Digit(16) => write!(f, "hex digit"),
+            Expected::Digit(_) => write!(f, "digit"),
+            Expected::Suffix => write!(f, "type suffix"),
+            Expected::InRange(ty) => write!(f, "value in range of `{}`", ty),
+        }
+    }
+}
+
+/// A parse error, located at the token the parser tripped over.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    pub span: Span,
+    pub expected: Vec<Expected>,
+    /// The offending token, `None` at the end of input.
+    pub found: Option<String>,
+    /// Where in the grammar the error happened, e.g. "after function signature".
+    pub context: Option<&'static str>,
+}
+
+impl Error {
+    pub fn new(input: Input, expected: Vec<Expected>) -> Error {
+        let token = input.peek();
+        Error {
+            span: token.span,
+            expected,
+            found: token.found().map(String::from),
+            context: None,
+        }
+    }
+
+    /// Whether this error sits right at the start of `input`,
+    /// i.e. the parser that was handed `input` did not get anywhere.
+    pub fn is_at(&self, input: Input) -> bool {
+        self.span.start == input.peek().span.start
+    }
+
+    pub fn location(&self, src: &str) -> LineCol {
+        self.span.line_col(src)
+    }
+
+    /// Whether a name was expected but a keyword found, e.g. `let while`.
+    fn found_keyword(&self) -> bool {
+        let expects_name = self
+            .expected
+            .iter()
+            .any(|e| matches!(e, Expected::Token(TokenKind::Ident) | Expected::Type));
+
+        expects_name && self.found.as_deref().and_then(TokenKind::keyword).is_some()
+    }
+}
+
+impl<'a> ParseError<Input<'a>> for Error {
+    fn from_error_kind(input: Input<'a>, _: ErrorKind) -> Self {
+        Error::new(input, Vec::new())
+    }
+
+    fn append(_: Input<'a>, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    /// The parsers work on tokens, not characters, so there's nothing to add.
+    fn from_char(input: Input<'a>, _: char) -> Self {
+        Error::new(input, Vec::new())
+    }
+
+    /// Keeps whichever error got further, merging what was expected
+    /// when both alternatives failed at the same token.
+    fn or(mut self, other: Self) -> Self {
+        if self.span.start > other.span.start {
+            return self;
+        }
+        if self.span.start < other.span.start {
+            return other;
+        }
+
+        for expected in other.expected {
+            if !self.expected.contains(&expected) {
+                self.expected.push(expected);
+            }
+        }
+        self.context = self.context.or(other.context);
+        self
+    }
+
+    /// Only errors at the very start of the annotated parser get its context,
+    /// errors further in are better described by their own.
+    fn add_context(input: Input<'a>, ctx: &'static str, mut other: Self) -> Self {
+        if other.context.is_none() && other.is_at(input) {
+            other.context = Some(ctx);
+        }
+        other
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.expected.as_slice() {
+            [] => write!(f, "unexpected token")?,
+            [one] => write!(f, "expected {}", one)?,
+            [a, b] => write!(f, "expected {} or {}", a, b)?,
+            [first, rest @ ..] => {
+                write!(f, "expected one of {}", first)?;
+                for expected in rest {
+                    write!(f, ", {}", expected)?;
+                }
+            }
+        }
+
+        if let Some(context) = self.context {
+            write!(f, " {}", context)?;
+        }
+
+        match &self.found {
+            Some(found) if self.found_keyword() => write!(f, ", found keyword `{}`", found),
+            Some(found) => write!(f, ", found `{}`", found),
+            None => write!(f, ", found end of input"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::lex;
+    use nom::Slice;
+
+    #[test]
+    fn found_skips_whitespace() {
+        let src = "let  \n  fibonaci(";
+        let tokens = lex(src);
+        let error = Error::new(
+            Input::new(&tokens).slice(1..),
+            vec![Expected::Token(TokenKind::Ident)],
+        );
+
+        assert_eq!(error.span.text(src), "fibonaci");
+        assert_eq!(error.location(src), LineCol { line: 2, column: 3 });
+        assert_eq!(error.to_string(), "expected identifier, found `fibonaci`");
+    }
+
+    #[test]
+    fn merges_alternatives() {
+        let tokens = lex("x");
+        let input = Input::new(&tokens);
+        let a = Error::new(input, vec![Expected::Token(TokenKind::LParen)]);
+        let b = Error::new(input, vec![Expected::Token(TokenKind::Ident)]);
+        let c = Error::new(input, vec![Expected::Token(TokenKind::Eof)]);
+
+        assert_eq!(
+            a.or(b).or(c).to_string(),
+            "expected one of `(`, identifier, end of input, found `x`"
+        );
+    }
+
+    #[test]
+    fn furthest_wins() {
+        let tokens = lex("a b");
+        let input = Input::new(&tokens);
+        let near = Error::new(input, vec![Expected::Token(TokenKind::Ident)]);
+        let far = Error::new(input.slice(1..), vec![Expected::Type]);
+
+        assert_eq!(near.or(far.clone()), far);
+    }
+
+    #[test]
+    fn end_of_input() {
+        let tokens = lex("a ");
+        let error = Error::new(Input::new(&tokens).slice(1..), vec![Expected::Type]);
+
+        assert_eq!(error.to_string(), "expected type, found end of input");
+    }
+}
@@ -1,169 +1,1201 @@
 use crate::parse::*;
 use nom::{
-    branch::alt, bytes::complete::tag, combinator::map, multi::separated_nonempty_list,
-    sequence::preceded, IResult,
+    branch::alt,
+    combinator::{cut, map, opt},
+    error::context,
+    multi::separated_list,
+    sequence::{delimited, pair, preceded, separated_pair, terminated},
+    Slice,
 };
-use util::{skip_whitespace, tag_ws};
+use util::spanned;
 
-// Note, using a Vec here isn't nice, since it's length is expected to be >2,
-// the chosen type `Vec` does not reflect that. e.g. parse, don't validate
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub enum Expression {
-    Equals(Vec<Expression>),
-    NotEqual(Vec<Expression>),
-    Or(Vec<Expression>),
-    And(Vec<Expression>),
-    Add(Vec<Expression>),
-    Subtract(Vec<Expression>),
-    Multiply(Vec<Expression>),
-    Divide(Vec<Expression>),
-    Not(Box<Expression>),
-    Negative(Box<Expression>),
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExpressionKind {
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expression>,
+    },
+    /// `[a, b, c]`
+    Array(Vec<Expression>),
+    /// `(a, b)`, `(a,)`, or `()` for no value at all.
+    Tuple(Vec<Expression>),
+    /// `target[index]`
+    Index {
+        target: Box<Expression>,
+        index: Box<Expression>,
+    },
+    /// `Name { field: value, .. }`
+    Struct {
+        name: Ident,
+        fields: Vec<(Ident, Expression)>,
+    },
+    /// `callee(arguments)`. A method call `x.f(a)` is read as `f(x, a)`.
+    Call {
+        callee: Box<Expression>,
+        arguments: Tuple,
+    },
+    /// `target.field`
+    Field {
+        target: Box<Expression>,
+        field: Ident,
+    },
+    /// `target.0`
+    Element {
+        target: Box<Expression>,
+        index: usize,
+    },
+    /// `match scrutinee { pattern => body, .. }`
+    Match {
+        scrutinee: Box<Expression>,
+        arms: Vec<MatchArm>,
+    },
+    /// `if condition { .. } else { .. }`, valued by the branch taken.
+    If {
+        condition: Box<Expression>,
+        then: Box<Body>,
+        otherwise: Option<Box<Body>>,
+    },
+    /// `{ statements }`, valued by its last expression.
+    Block(Box<Body>),
+    /// `|x: u64| x + 1` or `function(x: u64) -> u64 { .. }`, an anonymous
+    /// function that captures the variables around it.
+    Lambda {
+        args: Vec<(Ident, Type)>,
+        return_type: Option<Type>,
+        body: Box<Body>,
+    },
     Value(value::Value),
     Literal(Literal),
 }
 
-#[cfg(test)]
-mod test_expression {
-    use super::*;
-    #[test]
-    fn value() {
-        assert_eq!(
-            Expression::parse("true"),
-            Ok(("", Expression::Value(Value::Boolean(true))),)
-        );
+/// `pattern => { statements }`, or `pattern => value`, which is short for
+/// `pattern => { value }`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Body,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Equals,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Not,
+    Negative,
+}
+
+/// Every binary operator with its binding power, higher binds tighter.
+/// All of them are left associative. `==` and `!=` bind looser than `|`
+/// and `&`, so `a == b | c` compares `a` with `b | c`.
+const BINARY: &[(TokenKind, BinaryOp, u8)] = &[
+    (TokenKind::EqEq, BinaryOp::Equals, 1),
+    (TokenKind::NotEq, BinaryOp::NotEqual, 1),
+    (TokenKind::Pipe, BinaryOp::Or, 2),
+    (TokenKind::Amp, BinaryOp::And, 3),
+    (TokenKind::Lt, BinaryOp::Less, 4),
+    (TokenKind::Gt, BinaryOp::Greater, 4),
+    (TokenKind::LtEq, BinaryOp::LessEqual, 4),
+    (TokenKind::GtEq, BinaryOp::GreaterEqual, 4),
+    (TokenKind::Plus, BinaryOp::Add, 5),
+    (TokenKind::Minus, BinaryOp::Subtract, 5),
+    (TokenKind::Star, BinaryOp::Multiply, 6),
+    (TokenKind::Slash, BinaryOp::Divide, 6),
+];
+
+/// Every prefix operator, they bind tighter than any binary operator.
+const UNARY: &[(TokenKind, UnaryOp)] = &[
+    (TokenKind::Bang, UnaryOp::Not),
+    (TokenKind::Minus, UnaryOp::Negative),
+];
+
+impl From<ExpressionKind> for Expression {
+    fn from(kind: ExpressionKind) -> Expression {
+        Expression {
+            kind,
+            span: Span::default(),
+        }
     }
+}
 
-    #[test]
-    fn literal() {
-        assert_eq!(
-            Expression::parse("hannover"),
-            Ok((
-                "",
-                Expression::Literal(Literal {
-                    ident: Ident("hannover".to_string()),
-                    call_arguments: None
-                })
-            ))
-        );
+impl Expression {
+    /// The variable this expression refers to a part of, if it is
+    /// something that can be assigned to, like `x`, `a[i][j]` or `p.0.x`.
+    pub fn place_root(&self) -> Option<&Ident> {
+        match &self.kind {
+            ExpressionKind::Literal(Literal { ident, .. }) => Some(ident),
+            ExpressionKind::Index { target, .. }
+            | ExpressionKind::Field { target, .. }
+            | ExpressionKind::Element { target, .. } => target.place_root(),
+            _ => None,
+        }
+    }
+
+    /// The expressions directly inside this one, except those in bodies,
+    /// like the arms of a match or the branches of an `if`.
+    pub fn children(&self) -> Vec<&Expression> {
+        match &self.kind {
+            ExpressionKind::Binary { lhs, rhs, .. } => vec![lhs, rhs],
+            ExpressionKind::Unary { operand, .. } => vec![operand],
+            ExpressionKind::Array(items) | ExpressionKind::Tuple(items) => items.iter().collect(),
+            ExpressionKind::Index { target, index } => vec![target, index],
+            ExpressionKind::Struct { fields, .. } => fields.iter().map(|(_, e)| e).collect(),
+            ExpressionKind::Field { target, .. } | ExpressionKind::Element { target, .. } => {
+                vec![target]
+            }
+            ExpressionKind::Match { scrutinee, .. } => vec![scrutinee],
+            ExpressionKind::If { condition, .. } => vec![condition],
+            ExpressionKind::Block(_) | ExpressionKind::Lambda { .. } => vec![],
+            ExpressionKind::Value(_) => vec![],
+            ExpressionKind::Call { callee, arguments } => {
+                std::iter::once(&**callee).chain(&arguments.items).collect()
+            }
+            ExpressionKind::Literal(_) => vec![],
+        }
+    }
+
+    /// Like `children`, but for rewriting them.
+    pub fn children_mut(&mut self) -> Vec<&mut Expression> {
+        match &mut self.kind {
+            ExpressionKind::Binary { lhs, rhs, .. } => vec![lhs, rhs],
+            ExpressionKind::Unary { operand, .. } => vec![operand],
+            ExpressionKind::Array(items) | ExpressionKind::Tuple(items) => {
+                items.iter_mut().collect()
+            }
+            ExpressionKind::Index { target, index } => vec![target, index],
+            ExpressionKind::Struct { fields, .. } => fields.iter_mut().map(|(_, e)| e).collect(),
+            ExpressionKind::Field { target, .. } | ExpressionKind::Element { target, .. } => {
+                vec![target]
+            }
+            ExpressionKind::Match { scrutinee, .. } => vec![scrutinee],
+            ExpressionKind::If { condition, .. } => vec![condition],
+            ExpressionKind::Block(_) | ExpressionKind::Lambda { .. } => vec![],
+            ExpressionKind::Value(_) => vec![],
+            ExpressionKind::Call { callee, arguments } => std::iter::once(&mut **callee)
+                .chain(&mut arguments.items)
+                .collect(),
+            ExpressionKind::Literal(_) => vec![],
+        }
+    }
+
+    /// Collects the syntax errors in this expression and the bodies in it.
+    pub(super) fn collect_errors(&self, errors: &mut Vec<Error>) {
+        match &self.kind {
+            ExpressionKind::Match { arms, .. } => {
+                for arm in arms {
+                    arm.body.collect_errors(errors);
+                }
+            }
+            ExpressionKind::If {
+                then, otherwise, ..
+            } => {
+                then.collect_errors(errors);
+                if let Some(otherwise) = otherwise {
+                    otherwise.collect_errors(errors);
+                }
+            }
+            ExpressionKind::Block(body) | ExpressionKind::Lambda { body, .. } => {
+                body.collect_errors(errors)
+            }
+            _ => {}
+        }
+        for child in self.children() {
+            child.collect_errors(errors);
+        }
     }
 }
 
 impl Parse for Expression {
-    fn parse(input: &str) -> IResult<&str, Self> {
-        eq(input)
+    fn parse(input: Input) -> ParseResult<Self> {
+        util::expecting(Expected::Expression, |i| binary(i, 0))(input)
     }
 }
 
-#[inline]
-fn eq(i: &str) -> IResult<&str, Expression> {
-    map_vec(
-        separated_nonempty_list(tag_ws("=="), skip_whitespace(nq)),
-        Expression::Equals,
-    )(i)
-}
+/// Parses operands joined by binary operators that bind at least as tight
+/// as `min_power`. The right operand only takes operators binding tighter
+/// than its own, which makes `a - b + c` come out as `(a - b) + c`.
+fn binary(input: Input, min_power: u8) -> ParseResult<Expression> {
+    let (mut input, mut lhs) = unary(input)?;
 
-#[inline]
-fn nq(i: &str) -> IResult<&str, Expression> {
-    map_vec(
-        separated_nonempty_list(tag_ws("!="), skip_whitespace(or)),
-        Expression::NotEqual,
-    )(i)
+    loop {
+        let (op, power) = match BINARY.iter().find(|(t, _, _)| *t == input.peek().kind) {
+            Some((_, op, power)) if *power >= min_power => (*op, *power),
+            _ => break,
+        };
+
+        let (rest, rhs) = operand(|i| binary(i, power + 1))(input.slice(1..))?;
+        input = rest;
+        lhs = Expression {
+            span: lhs.span.to(rhs.span),
+            kind: ExpressionKind::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+        };
+    }
+
+    Ok((input, lhs))
 }
 
-#[inline]
-fn or(i: &str) -> IResult<&str, Expression> {
-    map_vec(
-        separated_nonempty_list(tag_ws("|"), skip_whitespace(and)),
-        Expression::Or,
-    )(i)
+fn unary(input: Input) -> ParseResult<Expression> {
+    let op = match UNARY.iter().find(|(t, _)| *t == input.peek().kind) {
+        Some((_, op)) => *op,
+        None => return postfix(input),
+    };
+
+    let (rest, operand) = match op {
+        UnaryOp::Negative => alt((negated_number, operand(unary)))(input.slice(1..))?,
+        UnaryOp::Not => operand(unary)(input.slice(1..))?,
+    };
+    Ok((
+        rest,
+        Expression {
+            span: input.span_to(&rest),
+            kind: ExpressionKind::Unary {
+                op,
+                operand: Box::new(operand),
+            },
+        },
+    ))
 }
 
-#[inline]
-fn and(i: &str) -> IResult<&str, Expression> {
-    map_vec(
-        separated_nonempty_list(tag_ws("&"), skip_whitespace(add)),
-        Expression::And,
-    )(i)
+/// A number right after a `-`, which may be the smallest value of a signed
+/// type, like `-128i8`. Not taken if the number is the target of a call,
+/// index or field, which the `-` applies to as a whole.
+fn negated_number(input: Input) -> ParseResult<Expression> {
+    let (rest, (value, span)) = spanned(value::parse_negated_number)(input)?;
+    if let TokenKind::LParen | TokenKind::LBracket | TokenKind::Dot = rest.peek().kind {
+        return Err(nom::Err::Error(Error::new(
+            input,
+            vec![Expected::Expression],
+        )));
+    }
+    Ok((
+        rest,
+        Expression {
+            kind: ExpressionKind::Value(value),
+            span,
+        },
+    ))
 }
 
-#[inline]
-fn add(i: &str) -> IResult<&str, Expression> {
-    map_vec(
-        separated_nonempty_list(tag_ws("+"), skip_whitespace(subtract)),
-        Expression::Add,
-    )(i)
+/// An operator has to be followed by its operand.
+fn operand<'a>(
+    f: impl Fn(Input<'a>) -> ParseResult<'a, Expression>,
+) -> impl Fn(Input<'a>) -> ParseResult<'a, Expression> {
+    cut(util::expecting(Expected::Expression, f))
 }
 
-#[inline]
-fn subtract(i: &str) -> IResult<&str, Expression> {
-    map_vec(
-        separated_nonempty_list(tag_ws("-"), skip_whitespace(multiply)),
-        Expression::Subtract,
-    )(i)
+/// An operand followed by any number of calls, `[index]`es, `.field`s,
+/// `.0` tuple elements and `.method(arguments)` calls.
+pub(super) fn postfix(input: Input) -> ParseResult<Expression> {
+    let (mut input, mut target) = literal(input)?;
+
+    loop {
+        let start = target.span;
+        let (rest, kind) = match input.peek().kind {
+            TokenKind::LParen => {
+                let (rest, arguments) = Tuple::parse(input)?;
+                let kind = ExpressionKind::Call {
+                    callee: Box::new(target),
+                    arguments,
+                };
+                (rest, kind)
+            }
+            TokenKind::LBracket => {
+                let (rest, index) = util::delimited_bracket(Expression::parse)(input)?;
+                let kind = ExpressionKind::Index {
+                    target: Box::new(target),
+                    index: Box::new(index),
+                };
+                (rest, kind)
+            }
+            TokenKind::Dot if tuple_index(input.slice(1..)) => {
+                let indices = tuple_indices(input.slice(1..))?;
+                let ((index, _), outer) = indices.split_last().expect("a token has some text");
+                // `t.0.1` is lexed as `t`, `.` and the float `0.1`.
+                for (index, span) in outer {
+                    target = Expression {
+                        span: start.to(*span),
+                        kind: ExpressionKind::Element {
+                            target: Box::new(target),
+                            index: *index,
+                        },
+                    };
+                }
+                let kind = ExpressionKind::Element {
+                    target: Box::new(target),
+                    index: *index,
+                };
+                (input.slice(2..), kind)
+            }
+            TokenKind::Dot => {
+                let (rest, method) = cut(context("after `.`", Literal::parse))(input.slice(1..))?;
+                match opt(Tuple::parse)(rest)? {
+                    (rest, Some(mut arguments)) => {
+                        arguments.items.insert(0, target);
+                        let kind = ExpressionKind::Call {
+                            callee: Box::new(Expression {
+                                span: method.span,
+                                kind: ExpressionKind::Literal(method),
+                            }),
+                            arguments,
+                        };
+                        (rest, kind)
+                    }
+                    (rest, None) if method.type_arguments.is_empty() => {
+                        let kind = ExpressionKind::Field {
+                            target: Box::new(target),
+                            field: method.ident,
+                        };
+                        (rest, kind)
+                    }
+                    (rest, None) => {
+                        return Err(nom::Err::Failure(Error::new(
+                            rest,
+                            vec![Expected::Token(TokenKind::LParen)],
+                        )))
+                    }
+                }
+            }
+            _ => return Ok((input, target)),
+        };
+
+        target = Expression {
+            span: start.to(input.span_to(&rest)),
+            kind,
+        };
+        input = rest;
+    }
 }
 
-#[inline]
-fn multiply(i: &str) -> IResult<&str, Expression> {
-    map_vec(
-        separated_nonempty_list(tag_ws("*"), skip_whitespace(divide)),
-        Expression::Multiply,
-    )(i)
+fn tuple_index(input: Input) -> bool {
+    matches!(input.peek().kind, TokenKind::Integer | TokenKind::Float)
 }
 
-#[inline]
-fn divide(i: &str) -> IResult<&str, Expression> {
-    map_vec(
-        separated_nonempty_list(tag_ws("/"), skip_whitespace(not)),
-        Expression::Divide,
-    )(i)
+/// The indices in the number token after a `.`, each with its span.
+fn tuple_indices(input: Input) -> Result<Vec<(usize, Span)>, nom::Err<Error>> {
+    let token = input.peek();
+    let mut start = token.span.start;
+    let mut indices = Vec::new();
+
+    for digits in token.text.split('.') {
+        let span = Span::new(start, start + digits.len());
+        match digits.parse() {
+            Ok(index) if digits.bytes().all(|b| b.is_ascii_digit()) => indices.push((index, span)),
+            _ => {
+                return Err(nom::Err::Failure(Error {
+                    context: Some("after `.`"),
+                    ..Error::new(input, vec![Expected::TupleIndex])
+                }))
+            }
+        }
+        start = span.end + 1;
+    }
+
+    Ok(indices)
 }
 
 #[inline]
-fn not(i: &str) -> IResult<&str, Expression> {
+fn literal(i: Input) -> ParseResult<Expression> {
     alt((
-        map(preceded(tag("!"), literal), |e| {
-            Expression::Not(Box::new(e))
+        value,
+        lambda,
+        match_expression,
+        block_like,
+        struct_literal,
+        map(
+            spanned(util::delimited_bracket(separated_list(
+                util::token(TokenKind::Comma),
+                Expression::parse,
+            ))),
+            |(items, span)| Expression {
+                kind: ExpressionKind::Array(items),
+                span,
+            },
+        ),
+        map(Literal::parse, |l| Expression {
+            span: l.span,
+            kind: ExpressionKind::Literal(l),
         }),
-        negative,
     ))(i)
 }
 
-#[inline]
-fn negative(i: &str) -> IResult<&str, Expression> {
-    alt((
-        map(preceded(tag("-"), literal), |e| {
-            Expression::Negative(Box::new(e))
-        }),
-        literal,
+fn match_expression(i: Input) -> ParseResult<Expression> {
+    let (rest, scrutinee) = preceded(
+        keyword::Match::parse,
+        cut(context("after `match`", condition)),
+    )(i)?;
+    let (mut rest, _) = cut(context(
+        "after `match` value",
+        util::token(TokenKind::LBrace),
+    ))(rest)?;
+
+    // Arms are separated by commas, which are optional after a `}`.
+    let mut arms = Vec::new();
+    while rest.peek().kind != TokenKind::RBrace {
+        let (after, arm) = cut(MatchArm::parse)(rest)?;
+        let consumed = &rest.tokens[..rest.tokens.len() - after.tokens.len()];
+        let after_brace = consumed.last().map(|t| t.kind) == Some(TokenKind::RBrace);
+        arms.push(arm);
+        rest = after;
+
+        match rest.peek().kind {
+            TokenKind::Comma => rest = rest.slice(1..),
+            TokenKind::RBrace => {}
+            _ if after_brace => {}
+            _ => {
+                let expected = vec![
+                    Expected::Token(TokenKind::Comma),
+                    Expected::Token(TokenKind::RBrace),
+                ];
+                return Err(nom::Err::Failure(Error::new(rest, expected)));
+            }
+        }
+    }
+    let (rest, _) = util::token(TokenKind::RBrace)(rest)?;
+
+    Ok((
+        rest,
+        Expression {
+            span: i.span_to(&rest),
+            kind: ExpressionKind::Match {
+                scrutinee: Box::new(scrutinee),
+                arms,
+            },
+        },
+    ))
+}
+
+/// The expression after `if`, `while` or `match`. It can't be a block, the
+/// `{` after the keyword starts the body, so `if {}` lacks a condition.
+pub(super) fn condition(i: Input) -> ParseResult<Expression> {
+    if i.peek().kind == TokenKind::LBrace {
+        return Err(nom::Err::Error(Error::new(i, vec![Expected::Expression])));
+    }
+    Expression::parse(i)
+}
+
+/// An `if` or a block, the expressions that end with a `}` of their own.
+pub(super) fn block_like(i: Input) -> ParseResult<Expression> {
+    alt((if_expression, block))(i)
+}
+
+fn if_expression(i: Input) -> ParseResult<Expression> {
+    let (rest, condition) = preceded(keyword::If::parse, cut(context("after `if`", condition)))(i)?;
+    let (rest, then) = cut(context("after `if` condition", Body::parse_block))(rest)?;
+    let (rest, otherwise) = opt(preceded(
+        keyword::Else::parse,
+        cut(context("after `else`", alt((Body::parse_block, else_if)))),
+    ))(rest)?;
+
+    Ok((
+        rest,
+        Expression {
+            span: i.span_to(&rest),
+            kind: ExpressionKind::If {
+                condition: Box::new(condition),
+                then: Box::new(then),
+                otherwise: otherwise.map(Box::new),
+            },
+        },
+    ))
+}
+
+/// The `if` in `else if`, read as a body holding just that `if`.
+fn else_if(i: Input) -> ParseResult<Body> {
+    let (rest, value) = if_expression(i)?;
+    let statement = Statement {
+        span: value.span,
+        kind: StatementKind::Expression(Box::new(value)),
+    };
+    let body = Body {
+        statements: vec![statement],
+        span: i.span_to(&rest),
+    };
+    Ok((rest, body))
+}
+
+fn block(i: Input) -> ParseResult<Expression> {
+    map(spanned(Body::parse_block), |(body, span)| Expression {
+        kind: ExpressionKind::Block(Box::new(body)),
+        span,
+    })(i)
+}
+
+impl Parse for MatchArm {
+    fn parse(input: Input) -> ParseResult<Self> {
+        let (rest, pattern) = Pattern::parse(input)?;
+        let (rest, _) = cut(context("after pattern", util::token(TokenKind::FatArrow)))(rest)?;
+        let (rest, body) = body_or_value("after `=>`")(rest)?;
+
+        Ok((
+            rest,
+            MatchArm {
+                pattern,
+                body,
+                span: input.span_to(&rest),
+            },
+        ))
+    }
+}
+
+/// `{ statements }`, or a single value, which is short for `{ value }`.
+/// `after` describes where a missing value is expected.
+fn body_or_value<'a>(after: &'static str) -> impl Fn(Input<'a>) -> ParseResult<'a, Body> {
+    move |input| {
+        if input.peek().kind == TokenKind::LBrace {
+            return Body::parse_block(input);
+        }
+
+        let (rest, value) = cut(context(after, Expression::parse))(input)?;
+        let statement = Statement {
+            span: value.span,
+            kind: StatementKind::Expression(Box::new(value)),
+        };
+        let body = Body {
+            statements: vec![statement],
+            span: input.span_to(&rest),
+        };
+        Ok((rest, body))
+    }
+}
+
+/// `|args| value`, `|args| -> Type { .. }` or `function(args) -> Type { .. }`.
+fn lambda(i: Input) -> ParseResult<Expression> {
+    map(
+        spanned(alt((closure, anonymous_function))),
+        |(kind, span)| Expression { kind, span },
+    )(i)
+}
+
+fn closure(i: Input) -> ParseResult<ExpressionKind> {
+    use util::token;
+
+    let (rest, args) = delimited(
+        token(TokenKind::Pipe),
+        parameters,
+        cut(token(TokenKind::Pipe)),
+    )(i)?;
+    let (rest, return_type) = return_type(rest)?;
+    let (rest, body) = match return_type {
+        Some(_) => cut(context("after return type", Body::parse_block))(rest)?,
+        None => body_or_value("after lambda parameters")(rest)?,
+    };
+
+    Ok((
+        rest,
+        ExpressionKind::Lambda {
+            args,
+            return_type,
+            body: Box::new(body),
+        },
+    ))
+}
+
+fn anonymous_function(i: Input) -> ParseResult<ExpressionKind> {
+    let (rest, args) = preceded(
+        keyword::Function::parse,
+        cut(context(
+            "after `function`",
+            util::delimited_paren(parameters),
+        )),
+    )(i)?;
+    let (rest, return_type) = return_type(rest)?;
+    let (rest, body) = cut(context("after function signature", Body::parse_block))(rest)?;
+
+    Ok((
+        rest,
+        ExpressionKind::Lambda {
+            args,
+            return_type,
+            body: Box::new(body),
+        },
+    ))
+}
+
+fn parameters(i: Input) -> ParseResult<Vec<(Ident, Type)>> {
+    separated_list(
+        util::token(TokenKind::Comma),
+        typed_ident("after parameter name"),
+    )(i)
+}
+
+fn return_type(i: Input) -> ParseResult<Option<Type>> {
+    opt(preceded(
+        util::token(TokenKind::Arrow),
+        cut(context("after `->`", Type::parse)),
     ))(i)
 }
 
-#[inline]
-fn literal(i: &str) -> IResult<&str, Expression> {
-    alt((value, map(Literal::parse, Expression::Literal)))(i)
+/// `Name { field: value, .. }`. Only taken when the `{` is followed by
+/// `field:`, so the block in `if x { .. }` isn't read as a literal of `x`.
+/// That is also why structs without fields have no literal.
+fn struct_literal(i: Input) -> ParseResult<Expression> {
+    use util::token;
+
+    let start = [
+        TokenKind::Ident,
+        TokenKind::LBrace,
+        TokenKind::Ident,
+        TokenKind::Colon,
+    ];
+    if !i
+        .tokens
+        .iter()
+        .map(|t| t.kind)
+        .take(4)
+        .eq(start.iter().copied())
+    {
+        return Err(nom::Err::Error(Error::new(i, Vec::new())));
+    }
+
+    let field = separated_pair(
+        Ident::parse,
+        token(TokenKind::Colon),
+        cut(context("after field name", Expression::parse)),
+    );
+    map(
+        spanned(pair(
+            Ident::parse,
+            delimited(
+                token(TokenKind::LBrace),
+                terminated(
+                    separated_list(token(TokenKind::Comma), field),
+                    opt(token(TokenKind::Comma)),
+                ),
+                cut(token(TokenKind::RBrace)),
+            ),
+        )),
+        |((name, fields), span)| Expression {
+            kind: ExpressionKind::Struct { name, fields },
+            span,
+        },
+    )(i)
 }
 
 #[inline]
-fn value(i: &str) -> IResult<&str, Expression> {
+fn value(i: Input) -> ParseResult<Expression> {
     alt((
-        map(Value::parse, Expression::Value),
-        util::delimited_paren(Expression::parse_ws),
+        map(spanned(Value::parse), |(v, span)| Expression {
+            kind: ExpressionKind::Value(v),
+            span,
+        }),
+        map(
+            spanned(util::parenthesised(Expression::parse)),
+            |(found, span)| match found {
+                util::Parenthesised::One(e) => Expression { span, ..e },
+                util::Parenthesised::Tuple(items) => Expression {
+                    kind: ExpressionKind::Tuple(items),
+                    span,
+                },
+            },
+        ),
     ))(i)
 }
 
-fn map_vec<'a, T>(
-    f: impl Fn(&'a str) -> IResult<&'a str, Vec<T>>,
-    g: impl Fn(Vec<T>) -> T,
-) -> impl Fn(&'a str) -> IResult<&'a str, T> {
-    move |s: &str| {
-        let (rest, mut res): (&str, Vec<T>) = f(s)?;
-        let len = res.len();
+#[cfg(test)]
+mod test_expression {
+    use super::*;
+    #[test]
+    fn value() {
+        assert_eq!(
+            Expression::parse_str("true"),
+            Ok(("", ExpressionKind::Value(Value::Boolean(true)).into()),)
+        );
+    }
+
+    #[test]
+    fn signed_minimums() {
+        let negative = |value| -> Expression {
+            ExpressionKind::Unary {
+                op: UnaryOp::Negative,
+                operand: Box::new(ExpressionKind::Value(value).into()),
+            }
+            .into()
+        };
+        assert_eq!(
+            parse("-128i8"),
+            negative(Value::Integer(128, Some(Suffix::I8)))
+        );
+        assert_eq!(
+            parse("-9223372036854775808i64"),
+            negative(Value::Integer(1 << 63, Some(Suffix::I64)))
+        );
+
+        let error = |src| match Expression::parse_str(src) {
+            Err(nom::Err::Failure(e)) => e.to_string(),
+            res => panic!("expected a failure, got {:?}", res),
+        };
+        assert_eq!(
+            error("128i8"),
+            "expected value in range of `i8`, found `128i8`"
+        );
+        assert_eq!(
+            error("-(128i8)"),
+            "expected value in range of `i8`, found `128i8`"
+        );
+        assert_eq!(
+            error("-128i8.abs()"),
+            "expected value in range of `i8`, found `128i8`"
+        );
+    }
+
+    #[test]
+    fn literal() {
+        assert_eq!(
+            Expression::parse_str("hannover"),
+            Ok((
+                "",
+                ExpressionKind::Literal(Literal::new(Ident::new("hannover"))).into()
+            ))
+        );
+    }
+
+    fn parse(src: &str) -> Expression {
+        Expression::parse_str(src).unwrap().1
+    }
+
+    fn binary_op(op: BinaryOp, lhs: &str, rhs: &str) -> Expression {
+        ExpressionKind::Binary {
+            op,
+            lhs: Box::new(parse(lhs)),
+            rhs: Box::new(parse(rhs)),
+        }
+        .into()
+    }
+
+    #[test]
+    fn relational() {
+        assert_eq!(parse("i < n"), binary_op(BinaryOp::Less, "i", "n"));
+        assert_eq!(parse("i>n"), binary_op(BinaryOp::Greater, "i", "n"));
+        assert_eq!(
+            parse("a + 1 <= b"),
+            binary_op(BinaryOp::LessEqual, "a + 1", "b")
+        );
+        assert_eq!(
+            parse("a >= b == c < d"),
+            binary_op(BinaryOp::Equals, "a >= b", "c < d")
+        );
+        assert_eq!(
+            parse("a < b & c > d"),
+            binary_op(BinaryOp::And, "a < b", "c > d")
+        );
+    }
+
+    #[test]
+    fn left_associative() {
+        assert_eq!(parse("a - b + c"), binary_op(BinaryOp::Add, "a - b", "c"));
+        assert_eq!(
+            parse("a / b * c"),
+            binary_op(BinaryOp::Multiply, "a / b", "c")
+        );
+        assert_eq!(
+            parse("a - b - c"),
+            binary_op(BinaryOp::Subtract, "a - b", "c")
+        );
+        assert_eq!(
+            parse("a - (b - c)"),
+            binary_op(BinaryOp::Subtract, "a", "b - c")
+        );
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(parse("a + b * c"), binary_op(BinaryOp::Add, "a", "b * c"));
+        assert_eq!(parse("a | b & c"), binary_op(BinaryOp::Or, "a", "b & c"));
+        assert_eq!(
+            parse("a == b | c"),
+            binary_op(BinaryOp::Equals, "a", "b | c")
+        );
+        assert_eq!(
+            parse("a & b != c"),
+            binary_op(BinaryOp::NotEqual, "a & b", "c")
+        );
+        assert_eq!(parse("a & b < c"), binary_op(BinaryOp::And, "a", "b < c"));
+        assert_eq!(parse("-a * b"), binary_op(BinaryOp::Multiply, "-a", "b"));
+        assert_eq!(
+            parse("!!x"),
+            ExpressionKind::Unary {
+                op: UnaryOp::Not,
+                operand: Box::new(parse("!x")),
+            }
+            .into()
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        assert_eq!(parse("[]"), ExpressionKind::Array(vec![]).into());
+        assert_eq!(
+            parse("[1, a + b, [c]]"),
+            ExpressionKind::Array(vec![parse("1"), parse("a + b"), parse("[c]")]).into()
+        );
+    }
+
+    #[test]
+    fn index() {
+        let index = |target: &str, index: &str| -> Expression {
+            ExpressionKind::Index {
+                target: Box::new(parse(target)),
+                index: Box::new(parse(index)),
+            }
+            .into()
+        };
+
+        assert_eq!(parse("a[i + 1]"), index("a", "i + 1"));
+        assert_eq!(parse("grid[y][x]"), index("grid[y]", "x"));
+        assert_eq!(parse("[1, 2][0]"), index("[1, 2]", "0"));
+        assert_eq!(
+            parse("-a[0]"),
+            ExpressionKind::Unary {
+                op: UnaryOp::Negative,
+                operand: Box::new(parse("a[0]")),
+            }
+            .into()
+        );
+
+        let src = "memo[n - 1] ";
+        assert_eq!(parse(src).span.text(src), "memo[n - 1]");
+    }
+
+    #[test]
+    fn structs() {
+        assert_eq!(
+            parse("Point { x: 1.0, y: a + b, }"),
+            ExpressionKind::Struct {
+                name: Ident::new("Point"),
+                fields: vec![
+                    (Ident::new("x"), parse("1.0")),
+                    (Ident::new("y"), parse("a + b")),
+                ],
+            }
+            .into()
+        );
+
+        let src = "Point { x: 1, y: 2 } ";
+        assert_eq!(parse(src).span.text(src), "Point { x: 1, y: 2 }");
+    }
+
+    #[test]
+    fn fields() {
+        let field = |target: &str, field: &str| -> Expression {
+            ExpressionKind::Field {
+                target: Box::new(parse(target)),
+                field: Ident::new(field),
+            }
+            .into()
+        };
+
+        assert_eq!(parse("p.x"), field("p", "x"));
+        assert_eq!(parse("line.start.x"), field("line.start", "x"));
+        assert_eq!(parse("points[0].x"), field("points[0]", "x"));
+        assert_eq!(parse("p.x * 2"), binary_op(BinaryOp::Multiply, "p.x", "2"));
+
+        let src = "a.b[0].c ";
+        assert_eq!(parse(src).span.text(src), "a.b[0].c");
+    }
+
+    #[test]
+    fn matches() {
+        let src = "match n {
+            0 => { zero() }
+            Some(x) => x + 1,
+            _ => n
+        } ";
+        let e = parse(src);
+        assert_eq!(e.span.text(src), src.trim_end());
+
+        let (scrutinee, arms) = match e.kind {
+            ExpressionKind::Match { scrutinee, arms } => (scrutinee, arms),
+            kind => panic!("expected a match, got {:?}", kind),
+        };
+        assert_eq!(*scrutinee, parse("n"));
+
+        let patterns: Vec<_> = arms.iter().map(|arm| arm.pattern.span.text(src)).collect();
+        assert_eq!(patterns, vec!["0", "Some(x)", "_"]);
+        assert_eq!(arms[1].span.text(src), "Some(x) => x + 1");
+        assert_eq!(
+            arms[1].body.statements(),
+            &[StatementKind::Expression(Box::new(parse("x + 1"))).into()]
+        );
+
+        assert_eq!(
+            parse("match x {} + 1"),
+            binary_op(BinaryOp::Add, "match x {}", "1")
+        );
+    }
+
+    #[test]
+    fn ifs_and_blocks() {
+        let src = "if n < 2 { n } else { f(n) } ";
+        let e = parse(src);
+        assert_eq!(e.span.text(src), src.trim_end());
+        let (condition, then, otherwise) = match e.kind {
+            ExpressionKind::If {
+                condition,
+                then,
+                otherwise: Some(otherwise),
+            } => (condition, then, otherwise),
+            kind => panic!("expected an if with an else, got {:?}", kind),
+        };
+        assert_eq!(*condition, binary_op(BinaryOp::Less, "n", "2"));
+        assert_eq!(then.value(), Some(&parse("n")));
+        assert_eq!(otherwise.value(), Some(&parse("f(n)")));
+
+        let src = "{ let x = 1\n x * 2 } ";
+        let e = parse(src);
+        assert_eq!(e.span.text(src), src.trim_end());
+        match e.kind {
+            ExpressionKind::Block(body) => {
+                assert_eq!(body.statements().len(), 2);
+                assert_eq!(body.value(), Some(&parse("x * 2")));
+            }
+            kind => panic!("expected a block, got {:?}", kind),
+        }
+
+        assert_eq!(
+            parse("if a { 1 } else { 2 } + 1"),
+            binary_op(BinaryOp::Add, "if a { 1 } else { 2 }", "1")
+        );
+        let (_, body) = Body::parse_str("return 1").unwrap();
+        assert_eq!(body.value(), None);
+    }
+
+    #[test]
+    fn else_if() {
+        let src = "if a { 1 } else if b { 2 } else { 3 } + 1 ";
+        let e = parse(src);
+        assert_eq!(e.span.text(src), src.trim_end());
+        let (lhs, rhs) = match e.kind {
+            ExpressionKind::Binary {
+                op: BinaryOp::Add,
+                lhs,
+                rhs,
+            } => (lhs, rhs),
+            kind => panic!("expected an addition, got {:?}", kind),
+        };
+        assert_eq!(*rhs, parse("1"));
+
+        let otherwise = match lhs.kind {
+            ExpressionKind::If {
+                otherwise: Some(otherwise),
+                ..
+            } => otherwise,
+            kind => panic!("expected an if with an else, got {:?}", kind),
+        };
+        assert_eq!(otherwise.statements().len(), 1);
+        assert_eq!(
+            otherwise.value().map(|e| e.span.text(src)),
+            Some("if b { 2 } else { 3 }")
+        );
+
+        match Expression::parse_str("if a { 1 } else while") {
+            Err(nom::Err::Failure(e)) => assert_eq!(
+                e.to_string(),
+                "expected `{` or `if` after `else`, found `while`"
+            ),
+            res => panic!("expected a failure, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn match_separators() {
+        let error = |src| match Expression::parse_str(src) {
+            Err(nom::Err::Failure(e)) => e.to_string(),
+            res => panic!("expected a failure, got {:?}", res),
+        };
+
+        assert_eq!(
+            error("match x { a => 1 b => 2 }"),
+            "expected `,` or `}`, found `b`"
+        );
+        assert_eq!(
+            error("match x { a 1 }"),
+            "expected `=>` after pattern, found `1`"
+        );
+        assert_eq!(
+            error("match x { a => 1,"),
+            "expected pattern, found end of input"
+        );
+    }
+
+    #[test]
+    fn lambdas() {
+        let lambda = |src| match parse(src).kind {
+            ExpressionKind::Lambda {
+                args,
+                return_type,
+                body,
+            } => (args, return_type, body),
+            kind => panic!("expected a lambda, got {:?}", kind),
+        };
+
+        let (args, return_type, body) = lambda("|x: u64, y: u64| x + y");
+        assert_eq!(
+            args,
+            vec![
+                (Ident::new("x"), Type::named("u64")),
+                (Ident::new("y"), Type::named("u64")),
+            ]
+        );
+        assert_eq!(return_type, None);
+        assert_eq!(
+            body.statements(),
+            &[StatementKind::Expression(Box::new(parse("x + y"))).into()]
+        );
+
+        let (args, return_type, body) = lambda("function(n: u64) -> bool { return n == 0 }");
+        assert_eq!(args.len(), 1);
+        assert_eq!(return_type, Some(Type::named("bool")));
+        assert_eq!(body.statements().len(), 1);
+
+        let (args, _, _) = lambda("|| 1");
+        assert_eq!(args, vec![]);
+
+        let src = "|x: u64| -> u64 { return x } ";
+        assert_eq!(parse(src).span.text(src), src.trim_end());
+        assert_eq!(parse("a | b"), binary_op(BinaryOp::Or, "a", "b"));
+    }
+
+    #[test]
+    fn tuples() {
+        let element = |target: &str, index| -> Expression {
+            ExpressionKind::Element {
+                target: Box::new(parse(target)),
+                index,
+            }
+            .into()
+        };
+
+        assert_eq!(parse("()"), ExpressionKind::Tuple(vec![]).into());
+        assert_eq!(parse("(a)"), parse("a"));
+        assert_eq!(
+            parse("(a,)"),
+            ExpressionKind::Tuple(vec![parse("a")]).into()
+        );
+        assert_eq!(
+            parse("(a, b + 1)"),
+            ExpressionKind::Tuple(vec![parse("a"), parse("b + 1")]).into()
+        );
+        assert_eq!(parse("t.0"), element("t", 0));
+        assert_eq!(parse("t.0.1"), element("t.0", 1));
+        assert_eq!(parse("t.0.1.x"), parse("(t.0).1.x"));
+        assert_eq!(parse("divmod(a, b).1"), element("divmod(a, b)", 1));
+
+        let src = "t.12.3 ";
+        match parse(src).kind {
+            ExpressionKind::Element { target, index: 3 } => {
+                assert_eq!(target.span.text(src), "t.12")
+            }
+            kind => panic!("expected an element, got {:?}", kind),
+        }
+        assert_eq!(parse(src).span.text(src), "t.12.3");
+
+        match Expression::parse_str("t.1u8") {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(e.to_string(), "expected tuple index after `.`, found `1u8`")
+            }
+            res => panic!("expected a failure, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn calls() {
+        let call = |callee: &str, arguments: &[&str]| -> Expression {
+            ExpressionKind::Call {
+                callee: Box::new(parse(callee)),
+                arguments: Tuple {
+                    items: arguments.iter().map(|a| parse(a)).collect(),
+                    span: Span::default(),
+                },
+            }
+            .into()
+        };
+
+        assert_eq!(parse("f(x, 1)"), call("f", &["x", "1"]));
+        assert_eq!(parse("f(x, 1,)"), call("f", &["x", "1"]));
+        assert_eq!(parse("x.f(a,)"), parse("f(x, a)"));
+        assert_eq!(parse("make_adder(1)(2)"), call("make_adder(1)", &["2"]));
+        assert_eq!(parse("(f)(x)"), call("f", &["x"]));
+        assert_eq!(parse("fs[0]()"), call("fs[0]", &[]));
+        assert_eq!(parse("(p.f)(a)"), call("p.f", &["a"]));
+        assert_eq!(parse("x.abs()"), parse("abs(x)"));
+        assert_eq!(parse("p.scale(2).x"), parse("scale(p, 2).x"));
+        assert_eq!(parse("a.f(b).g(c, d)"), parse("g(f(a, b), c, d)"));
+        assert_eq!(parse("a.max::<u64>(b)"), parse("max::<u64>(a, b)"));
+        match Expression::parse_str("p.x::<u64>") {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(e.to_string(), "expected `(`, found end of input")
+            }
+            res => panic!("expected a failure, got {:?}", res),
+        }
+
+        let src = "make_adder(1)(2) ";
+        assert_eq!(parse(src).span.text(src), "make_adder(1)(2)");
+        let src = "numbers.sum() ";
+        match parse(src).kind {
+            ExpressionKind::Call { callee, arguments } => {
+                assert_eq!(callee.span.text(src), "sum");
+                assert_eq!(arguments.items[0].span.text(src), "numbers");
+            }
+            kind => panic!("expected a call, got {:?}", kind),
+        }
+        assert_eq!(parse(src).span.text(src), "numbers.sum()");
+    }
+
+    #[test]
+    fn missing_operand() {
+        match Expression::parse_str("a + )") {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(e.to_string(), "expected expression, found `)`")
+            }
+            res => panic!("expected a failure, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn span() {
+        let src = "(a + b) * -c ";
+        let (_, e) = Expression::parse_str(src).unwrap();
+        assert_eq!(e.span.text(src), "(a + b) * -c");
 
-        if len == 1 {
-            Ok((rest, res.pop().unwrap()))
-        } else {
-            Ok((rest, g(res)))
+        match e.kind {
+            ExpressionKind::Binary {
+                op: BinaryOp::Multiply,
+                lhs,
+                rhs,
+            } => {
+                assert_eq!(lhs.span.text(src), "(a + b)");
+                assert_eq!(rhs.span.text(src), "-c");
+            }
+            kind => panic!("expected multiplication, got {:?}", kind),
         }
     }
 }
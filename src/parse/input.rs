@@ -0,0 +1,87 @@
+use crate::parse::{Span, Token, TokenKind};
+use nom::{InputLength, Slice};
+use std::ops::{Range, RangeFrom, RangeFull, RangeTo};
+
+/// Parser input: the tokens that are left to parse. The last token is
+/// always `TokenKind::Eof`, so there always is a next token to look at.
+#[derive(Clone, Copy, Debug)]
+pub struct Input<'a> {
+    pub tokens: &'a [Token<'a>],
+}
+
+impl<'a> Input<'a> {
+    pub fn new(tokens: &'a [Token<'a>]) -> Input<'a> {
+        debug_assert!(matches!(tokens.last(), Some(t) if t.kind == TokenKind::Eof));
+        Input { tokens }
+    }
+
+    /// The token a parser looks at next.
+    pub fn peek(&self) -> &'a Token<'a> {
+        &self.tokens[0]
+    }
+
+    /// Span of the tokens between the start of `self` and the start of `rest`,
+    /// where `rest` is what a parser left over after consuming `self`.
+    pub fn span_to(&self, rest: &Input) -> Span {
+        let consumed = self.tokens.len() - rest.tokens.len();
+        let start = self.peek().span.start;
+
+        match consumed {
+            0 => Span::new(start, start),
+            n => Span::new(start, self.tokens[n - 1].span.end),
+        }
+    }
+}
+
+/// Inputs are equal if they are the same position in the same token stream.
+impl<'a> PartialEq for Input<'a> {
+    fn eq(&self, other: &Input<'a>) -> bool {
+        std::ptr::eq(self.tokens, other.tokens)
+    }
+}
+
+impl<'a> Eq for Input<'a> {}
+
+impl<'a> InputLength for Input<'a> {
+    fn input_len(&self) -> usize {
+        self.tokens.len()
+    }
+}
+
+macro_rules! impl_slice {
+    ($range:ty) => {
+        impl<'a> Slice<$range> for Input<'a> {
+            fn slice(&self, range: $range) -> Self {
+                Input {
+                    tokens: &self.tokens[range],
+                }
+            }
+        }
+    };
+}
+
+impl_slice!(Range<usize>);
+impl_slice!(RangeTo<usize>);
+impl_slice!(RangeFrom<usize>);
+impl_slice!(RangeFull);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::lex;
+
+    #[test]
+    fn spans() {
+        let tokens = lex("hello  world ");
+        let input = Input::new(&tokens);
+
+        assert_eq!(input.peek().text, "hello");
+        assert_eq!(input.span_to(&input).len(), 0);
+
+        let rest = input.slice(2..);
+        assert_eq!(rest.peek().kind, TokenKind::Eof);
+        assert_eq!(input.span_to(&rest).text("hello  world "), "hello  world");
+        assert!(input.slice(1..) != rest);
+        assert!(input.slice(2..) == rest);
+    }
+}
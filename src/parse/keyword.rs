@@ -1,51 +1,180 @@
-use crate::parse::Parse;
-use nom::{bytes::complete::tag, combinator::map, IResult};
+use crate::parse::{util::token, Input, Parse, ParseResult, TokenKind};
+use nom::combinator::map;
+
+/// Keywords that start an item, where parsing resumes after any error.
+pub const ITEM: &[TokenKind] = &[
+    TokenKind::Function,
+    TokenKind::Struct,
+    TokenKind::Enum,
+    TokenKind::Import,
+    TokenKind::Use,
+    TokenKind::Const,
+    TokenKind::Global,
+];
+
+/// Whether `input` is at the start of an item. `function(` starts an
+/// anonymous function instead.
+pub fn starts_item(input: Input) -> bool {
+    match input.tokens {
+        [first, next, ..] if first.kind == TokenKind::Function => next.kind != TokenKind::LParen,
+        [first, ..] => ITEM.contains(&first.kind),
+        [] => false,
+    }
+}
+
+/// Keywords that start a statement, where parsing resumes after an error.
+pub const STATEMENT: &[TokenKind] = &[
+    TokenKind::Let,
+    TokenKind::If,
+    TokenKind::While,
+    TokenKind::For,
+    TokenKind::Break,
+    TokenKind::Continue,
+    TokenKind::Return,
+    TokenKind::Match,
+];
 
 pub struct Function {}
 
 impl Parse for Function {
-    fn parse(input: &str) -> IResult<&str, Function> {
-        map(tag("function"), |_| Function {})(input)
+    fn parse(input: Input) -> ParseResult<Function> {
+        map(token(TokenKind::Function), |_| Function {})(input)
+    }
+}
+
+pub struct Fn {}
+
+impl Parse for Fn {
+    fn parse(input: Input) -> ParseResult<Fn> {
+        map(token(TokenKind::Fn), |_| Fn {})(input)
+    }
+}
+
+pub struct Struct {}
+
+impl Parse for Struct {
+    fn parse(input: Input) -> ParseResult<Struct> {
+        map(token(TokenKind::Struct), |_| Struct {})(input)
+    }
+}
+
+pub struct Enum {}
+
+impl Parse for Enum {
+    fn parse(input: Input) -> ParseResult<Enum> {
+        map(token(TokenKind::Enum), |_| Enum {})(input)
+    }
+}
+
+pub struct Import {}
+
+impl Parse for Import {
+    fn parse(input: Input) -> ParseResult<Import> {
+        map(token(TokenKind::Import), |_| Import {})(input)
+    }
+}
+
+pub struct Use {}
+
+impl Parse for Use {
+    fn parse(input: Input) -> ParseResult<Use> {
+        map(token(TokenKind::Use), |_| Use {})(input)
+    }
+}
+
+pub struct Const {}
+
+impl Parse for Const {
+    fn parse(input: Input) -> ParseResult<Const> {
+        map(token(TokenKind::Const), |_| Const {})(input)
+    }
+}
+
+pub struct Global {}
+
+impl Parse for Global {
+    fn parse(input: Input) -> ParseResult<Global> {
+        map(token(TokenKind::Global), |_| Global {})(input)
+    }
+}
+
+pub struct Match {}
+
+impl Parse for Match {
+    fn parse(input: Input) -> ParseResult<Match> {
+        map(token(TokenKind::Match), |_| Match {})(input)
     }
 }
 
 pub struct If {}
 
 impl Parse for If {
-    fn parse(input: &str) -> IResult<&str, If> {
-        map(tag("if"), |_| If {})(input)
+    fn parse(input: Input) -> ParseResult<If> {
+        map(token(TokenKind::If), |_| If {})(input)
     }
 }
 
 pub struct Else {}
 
 impl Parse for Else {
-    fn parse(input: &str) -> IResult<&str, Else> {
-        map(tag("else"), |_| Else {})(input)
+    fn parse(input: Input) -> ParseResult<Else> {
+        map(token(TokenKind::Else), |_| Else {})(input)
     }
 }
 
 pub struct While {}
 
 impl Parse for While {
-    fn parse(input: &str) -> IResult<&str, While> {
-        map(tag("while"), |_| While {})(input)
+    fn parse(input: Input) -> ParseResult<While> {
+        map(token(TokenKind::While), |_| While {})(input)
+    }
+}
+
+pub struct For {}
+
+impl Parse for For {
+    fn parse(input: Input) -> ParseResult<For> {
+        map(token(TokenKind::For), |_| For {})(input)
+    }
+}
+
+pub struct In {}
+
+impl Parse for In {
+    fn parse(input: Input) -> ParseResult<In> {
+        map(token(TokenKind::In), |_| In {})(input)
+    }
+}
+
+pub struct Break {}
+
+impl Parse for Break {
+    fn parse(input: Input) -> ParseResult<Break> {
+        map(token(TokenKind::Break), |_| Break {})(input)
+    }
+}
+
+pub struct Continue {}
+
+impl Parse for Continue {
+    fn parse(input: Input) -> ParseResult<Continue> {
+        map(token(TokenKind::Continue), |_| Continue {})(input)
     }
 }
 
 pub struct Return {}
 
 impl Parse for Return {
-    fn parse(input: &str) -> IResult<&str, Return> {
-        map(tag("return"), |_| Return {})(input)
+    fn parse(input: Input) -> ParseResult<Return> {
+        map(token(TokenKind::Return), |_| Return {})(input)
     }
 }
 
 pub struct Let {}
 
 impl Parse for Let {
-    fn parse(input: &str) -> IResult<&str, Let> {
-        map(tag("let"), |_| Let {})(input)
+    fn parse(input: Input) -> ParseResult<Let> {
+        map(token(TokenKind::Let), |_| Let {})(input)
     }
 }
 
@@ -55,11 +184,23 @@ mod tests {
 
     #[test]
     fn keywords() {
-        assert_eq!(If::parse("if").unwrap().0, "");
-        assert_eq!(Else::parse("else").unwrap().0, "");
+        assert_eq!(Fn::parse_str("fn").unwrap().0, "");
+        assert_eq!(Struct::parse_str("struct").unwrap().0, "");
+        assert_eq!(Enum::parse_str("enum").unwrap().0, "");
+        assert_eq!(Import::parse_str("import").unwrap().0, "");
+        assert_eq!(Use::parse_str("use").unwrap().0, "");
+        assert_eq!(Const::parse_str("const").unwrap().0, "");
+        assert_eq!(Global::parse_str("global").unwrap().0, "");
+        assert_eq!(Match::parse_str("match").unwrap().0, "");
+        assert_eq!(If::parse_str("if").unwrap().0, "");
+        assert_eq!(Else::parse_str("else").unwrap().0, "");
 
-        assert_eq!(While::parse("while").unwrap().0, "");
-        assert_eq!(Return::parse("return").unwrap().0, "");
-        assert_eq!(Let::parse("let").unwrap().0, "");
+        assert_eq!(While::parse_str("while").unwrap().0, "");
+        assert_eq!(For::parse_str("for").unwrap().0, "");
+        assert_eq!(In::parse_str("in").unwrap().0, "");
+        assert_eq!(Break::parse_str("break").unwrap().0, "");
+        assert_eq!(Continue::parse_str("continue").unwrap().0, "");
+        assert_eq!(Return::parse_str("return").unwrap().0, "");
+        assert_eq!(Let::parse_str("let").unwrap().0, "");
     }
 }
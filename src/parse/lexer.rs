@@ -0,0 +1,523 @@
+use crate::parse::token::{Token, TokenKind, Trivia, TriviaKind, PUNCTUATION};
+use crate::parse::Span;
+use nom::IResult;
+
+/// Splits `src` into tokens, the last one always being `TokenKind::Eof`.
+/// Lexing never fails, characters that start no token become `TokenKind::Unknown`.
+pub fn lex(src: &str) -> Vec<Token<'_>> {
+    lex_at(src, 0)
+}
+
+/// Like `lex`, with spans starting at `start` instead of 0, for a file that
+/// comes after others in a program.
+pub fn lex_at(src: &str, start: usize) -> Vec<Token<'_>> {
+    let offset = |rest: &str| start + src.len() - rest.len();
+    let mut tokens = Vec::new();
+    let mut rest = src;
+
+    loop {
+        let mut leading_trivia = Vec::new();
+        while let Ok((after, kind)) = trivia(rest) {
+            leading_trivia.push(Trivia {
+                kind,
+                text: &rest[..rest.len() - after.len()],
+                span: Span::new(offset(rest), offset(after)),
+            });
+            rest = after;
+        }
+
+        let (after, kind) = match token(rest) {
+            Ok(result) => result,
+            Err(_) => (rest, TokenKind::Eof),
+        };
+        let text = &rest[..rest.len() - after.len()];
+
+        tokens.push(Token {
+            kind,
+            text,
+            span: Span::new(offset(rest), offset(after)),
+            leading_trivia,
+        });
+
+        if kind == TokenKind::Eof {
+            return tokens;
+        }
+        rest = after;
+    }
+}
+
+fn trivia(input: &str) -> IResult<&str, TriviaKind> {
+    use nom::{branch::alt, bytes::complete::take_while1, combinator::map};
+
+    alt((
+        map(take_while1(|c| "\n\r\t ".contains(c)), |_| {
+            TriviaKind::Whitespace
+        }),
+        doc_comment,
+        line_comment,
+        block_comment,
+    ))(input)
+}
+
+/// `///`, but not `////`, which is commented out code.
+fn doc_comment(input: &str) -> IResult<&str, TriviaKind> {
+    use nom::{
+        bytes::complete::{tag, take_while},
+        combinator::{map, not},
+        sequence::tuple,
+    };
+
+    map(
+        tuple((tag("///"), not(tag("/")), take_while(|c| c != '\n'))),
+        |_| TriviaKind::DocComment,
+    )(input)
+}
+
+fn line_comment(input: &str) -> IResult<&str, TriviaKind> {
+    use nom::{
+        bytes::complete::{tag, take_while},
+        combinator::map,
+        sequence::pair,
+    };
+
+    map(pair(tag("//"), take_while(|c| c != '\n')), |_| {
+        TriviaKind::LineComment
+    })(input)
+}
+
+/// A block comment, including the comments nested in it.
+/// Fails if the comment isn't closed, see `unterminated_comment`.
+fn block_comment(input: &str) -> IResult<&str, TriviaKind> {
+    use nom::{
+        bytes::complete::tag,
+        error::{ErrorKind, ParseError},
+    };
+
+    let (mut rest, _) = tag("/*")(input)?;
+    let mut depth = 1;
+
+    while depth > 0 {
+        if rest.starts_with("/*") {
+            depth += 1;
+            rest = &rest[2..];
+        } else if rest.starts_with("*/") {
+            depth -= 1;
+            rest = &rest[2..];
+        } else {
+            match rest.chars().next() {
+                Some(c) => rest = &rest[c.len_utf8()..],
+                None => {
+                    return Err(nom::Err::Error(ParseError::from_error_kind(
+                        input,
+                        ErrorKind::TakeUntil,
+                    )))
+                }
+            }
+        }
+    }
+
+    Ok((rest, TriviaKind::BlockComment))
+}
+
+fn token(input: &str) -> IResult<&str, TokenKind> {
+    use nom::branch::alt;
+
+    alt((
+        raw_string,
+        word,
+        number,
+        string,
+        label,
+        character,
+        unterminated_comment,
+        punctuation,
+        unknown,
+    ))(input)
+}
+
+/// A string with escapes, which are only checked when the value is parsed.
+fn string(input: &str) -> IResult<&str, TokenKind> {
+    use nom::character::complete::char;
+
+    let (mut rest, _) = char('"')(input)?;
+    loop {
+        let mut chars = rest.chars();
+        match chars.next() {
+            Some('"') => return Ok((chars.as_str(), TokenKind::Str)),
+            Some('\\') => {
+                chars.next();
+            }
+            Some(_) => {}
+            None => return Ok((rest, TokenKind::UnterminatedStr)),
+        }
+        rest = chars.as_str();
+    }
+}
+
+/// `r"..."`, or with `#`s around the quotes to allow `"` inside.
+fn raw_string(input: &str) -> IResult<&str, TokenKind> {
+    use nom::{
+        bytes::complete::{tag, take_while},
+        character::complete::char,
+        sequence::{preceded, terminated},
+    };
+
+    let (rest, hashes) =
+        preceded(char('r'), terminated(take_while(|c| c == '#'), char('"')))(input)?;
+    let end = format!("\"{}", hashes);
+
+    match rest.find(&end) {
+        Some(i) => {
+            let (rest, _) = tag(end.as_str())(&rest[i..])?;
+            Ok((rest, TokenKind::RawStr))
+        }
+        None => Ok((&rest[rest.len()..], TokenKind::UnterminatedStr)),
+    }
+}
+
+/// A loop label, `'` and a word that unlike a char literal isn't closed by
+/// another `'`.
+fn label(input: &str) -> IResult<&str, TokenKind> {
+    use nom::{character::complete::char, combinator::not, sequence::preceded};
+
+    let (rest, _) = preceded(char('\''), word)(input)?;
+    let (rest, _) = not(char('\''))(rest)?;
+    Ok((rest, TokenKind::Label))
+}
+
+/// A character literal, closed on the same line.
+/// Whether there is exactly one character in it is checked when the value is parsed.
+fn character(input: &str) -> IResult<&str, TokenKind> {
+    use nom::{
+        branch::alt,
+        bytes::complete::{is_not, take},
+        character::complete::char,
+        combinator::map,
+        multi::many1,
+        sequence::{delimited, preceded},
+    };
+
+    map(
+        delimited(
+            char('\''),
+            many1(alt((preceded(char('\\'), take(1usize)), is_not("\\'\n")))),
+            char('\''),
+        ),
+        |_| TokenKind::Char,
+    )(input)
+}
+
+fn unterminated_comment(input: &str) -> IResult<&str, TokenKind> {
+    use nom::{
+        bytes::complete::tag,
+        combinator::{map, rest},
+        sequence::pair,
+    };
+
+    map(pair(tag("/*"), rest), |_| TokenKind::UnterminatedComment)(input)
+}
+
+/// A keyword or identifier, which starts with a Unicode `XID_Start`
+/// character and goes on with `XID_Continue` ones, like `größe` or `x_1`.
+/// It may also start with `_` if more follows, a lone `_` is the wildcard.
+fn word(input: &str) -> IResult<&str, TokenKind> {
+    use nom::{
+        branch::alt,
+        bytes::complete::{take_while, take_while1},
+        character::complete::{anychar, char},
+        combinator::{recognize, verify},
+        sequence::pair,
+    };
+    use unicode_xid::UnicodeXID;
+
+    let (rest, word) = alt((
+        recognize(pair(
+            verify(anychar, |c: &char| c.is_xid_start()),
+            take_while(UnicodeXID::is_xid_continue),
+        )),
+        recognize(pair(char('_'), take_while1(UnicodeXID::is_xid_continue))),
+    ))(input)?;
+
+    Ok((rest, TokenKind::keyword(word).unwrap_or(TokenKind::Ident)))
+}
+
+/// A number and whatever letters follow it, which should be a type suffix.
+fn number(input: &str) -> IResult<&str, TokenKind> {
+    use nom::bytes::complete::take_while;
+
+    let (rest, kind) = number_body(input)?;
+    let (rest, _) = take_while(|c: char| c.is_ascii_alphanumeric() || c == '_')(rest)?;
+    Ok((rest, kind))
+}
+
+/// A number without its suffix: `0x`, `0o` or `0b` and digits in that radix,
+/// or a decimal with an optional fraction and exponent. `_` may separate digits.
+pub(super) fn number_body(input: &str) -> IResult<&str, TokenKind> {
+    use nom::{
+        branch::alt,
+        bytes::complete::{tag, take_while},
+        character::complete::{char, digit1, one_of},
+        combinator::opt,
+        sequence::tuple,
+    };
+    let digits = |radix| take_while(move |c: char| c.is_digit(radix) || c == '_');
+
+    let prefix: IResult<&str, &str> = alt((tag("0x"), tag("0o"), tag("0b")))(input);
+    if let Ok((rest, prefix)) = prefix {
+        let (rest, _) = digits(radix(prefix))(rest)?;
+        return Ok((rest, TokenKind::Integer));
+    }
+
+    let (rest, _) = tuple((digit1, digits(10)))(input)?;
+    let (rest, fraction) = opt(tuple((char('.'), digit1, digits(10))))(rest)?;
+    let (rest, exponent) = opt(tuple((one_of("eE"), opt(one_of("+-")), digit1, digits(10))))(rest)?;
+
+    match (fraction, exponent) {
+        (None, None) => Ok((rest, TokenKind::Integer)),
+        _ => Ok((rest, TokenKind::Float)),
+    }
+}
+
+/// The radix a number prefix like `0x` stands for.
+pub(super) fn radix(prefix: &str) -> u32 {
+    match prefix {
+        "0x" => 16,
+        "0o" => 8,
+        "0b" => 2,
+        _ => 10,
+    }
+}
+
+fn punctuation(input: &str) -> IResult<&str, TokenKind> {
+    use nom::error::{ErrorKind, ParseError};
+
+    match PUNCTUATION.iter().find(|(p, _)| input.starts_with(p)) {
+        Some((p, kind)) => Ok((&input[p.len()..], *kind)),
+        None => Err(nom::Err::Error(ParseError::from_error_kind(
+            input,
+            ErrorKind::Tag,
+        ))),
+    }
+}
+
+fn unknown(input: &str) -> IResult<&str, TokenKind> {
+    use nom::{character::complete::anychar, combinator::map};
+
+    map(anychar, |_| TokenKind::Unknown)(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TokenKind::*;
+
+    fn kinds(src: &str) -> Vec<(TokenKind, &str)> {
+        lex(src).into_iter().map(|t| (t.kind, t.text)).collect()
+    }
+
+    #[test]
+    fn keywords_and_identifiers() {
+        assert_eq!(
+            kinds("let letter = iffy"),
+            vec![
+                (Let, "let"),
+                (Ident, "letter"),
+                (Assign, "="),
+                (Ident, "iffy"),
+                (Eof, "")
+            ]
+        );
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        assert_eq!(
+            kinds("let größe_2 = Ωmega\u{308} + Ł·x ÷ 'schleife"),
+            vec![
+                (Let, "let"),
+                (Ident, "größe_2"),
+                (Assign, "="),
+                (Ident, "Ωmega\u{308}"),
+                (Plus, "+"),
+                (Ident, "Ł·x"),
+                (Unknown, "÷"),
+                (Label, "'schleife"),
+                (Eof, "")
+            ]
+        );
+        assert_eq!(
+            kinds("\u{308}a"),
+            vec![(Unknown, "\u{308}"), (Ident, "a"), (Eof, "")]
+        );
+    }
+
+    #[test]
+    fn underscore_identifiers() {
+        assert_eq!(
+            kinds("_x _ __ _1 '_a"),
+            vec![
+                (Ident, "_x"),
+                (Underscore, "_"),
+                (Ident, "__"),
+                (Ident, "_1"),
+                (Label, "'_a"),
+                (Eof, "")
+            ]
+        );
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(
+            kinds("_=>a->b==!c!=-1.5"),
+            vec![
+                (Underscore, "_"),
+                (FatArrow, "=>"),
+                (Ident, "a"),
+                (Arrow, "->"),
+                (Ident, "b"),
+                (EqEq, "=="),
+                (Bang, "!"),
+                (Ident, "c"),
+                (NotEq, "!="),
+                (Minus, "-"),
+                (Float, "1.5"),
+                (Eof, "")
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_characters() {
+        assert_eq!(
+            kinds("1 @ 2$"),
+            vec![
+                (Integer, "1"),
+                (Unknown, "@"),
+                (Integer, "2"),
+                (Unknown, "$"),
+                (Eof, "")
+            ]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let tokens = lex("a // b\n/* c /* d */ e */ f /// g\n//// h\n/* i");
+        let trivia: Vec<_> = tokens
+            .iter()
+            .flat_map(|t| &t.leading_trivia)
+            .filter(|t| t.kind != TriviaKind::Whitespace)
+            .map(|t| (t.kind, t.text))
+            .collect();
+
+        assert_eq!(
+            trivia,
+            vec![
+                (TriviaKind::LineComment, "// b"),
+                (TriviaKind::BlockComment, "/* c /* d */ e */"),
+                (TriviaKind::DocComment, "/// g"),
+                (TriviaKind::LineComment, "//// h"),
+            ]
+        );
+        assert_eq!(
+            tokens.iter().map(|t| (t.kind, t.text)).collect::<Vec<_>>(),
+            vec![
+                (Ident, "a"),
+                (Ident, "f"),
+                (UnterminatedComment, "/* i"),
+                (Eof, "")
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(
+            kinds("0xff_FFu8 0o17 0b1012 1_000 0.05 1e-9 2.5E+3f32 42u8 1.foo 3e"),
+            vec![
+                (Integer, "0xff_FFu8"),
+                (Integer, "0o17"),
+                (Integer, "0b1012"),
+                (Integer, "1_000"),
+                (Float, "0.05"),
+                (Float, "1e-9"),
+                (Float, "2.5E+3f32"),
+                (Integer, "42u8"),
+                (Integer, "1"),
+                (Dot, "."),
+                (Ident, "foo"),
+                (Integer, "3e"),
+                (Eof, "")
+            ]
+        );
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(
+            kinds(r##"'a' '\'' "a \" b" r#"c " d"# r"e" rest"##),
+            vec![
+                (Char, "'a'"),
+                (Char, r"'\''"),
+                (Str, r#""a \" b""#),
+                (RawStr, r##"r#"c " d"#"##),
+                (RawStr, r#"r"e""#),
+                (Ident, "rest"),
+                (Eof, "")
+            ]
+        );
+        assert_eq!(
+            kinds("x \"abc\n"),
+            vec![(Ident, "x"), (UnterminatedStr, "\"abc\n"), (Eof, "")]
+        );
+        assert_eq!(
+            kinds("'\n'"),
+            vec![(Unknown, "'"), (Unknown, "'"), (Eof, "")]
+        );
+        assert_eq!(
+            kinds("'outer: 'a' 'ab' 'b"),
+            vec![
+                (Label, "'outer"),
+                (Colon, ":"),
+                (Char, "'a'"),
+                (Char, "'ab'"),
+                (Label, "'b"),
+                (Eof, "")
+            ]
+        );
+        assert_eq!(
+            kinds("0..n 1..=2.5"),
+            vec![
+                (Integer, "0"),
+                (DotDot, ".."),
+                (Ident, "n"),
+                (Integer, "1"),
+                (DotDotEq, "..="),
+                (Float, "2.5"),
+                (Eof, "")
+            ]
+        );
+    }
+
+    #[test]
+    fn trivia_and_spans() {
+        let tokens = lex("  fib(\n n)\n");
+        let spans: Vec<_> = tokens.iter().map(|t| (t.span.start, t.span.end)).collect();
+        assert_eq!(spans, vec![(2, 5), (5, 6), (8, 9), (9, 10), (11, 11)]);
+
+        let trivia: Vec<_> = tokens
+            .iter()
+            .map(|t| {
+                t.leading_trivia
+                    .iter()
+                    .map(|t| (t.span.start, t.span.end))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(
+            trivia,
+            vec![vec![(0, 2)], vec![], vec![(6, 8)], vec![], vec![(10, 11)]]
+        );
+    }
+}
@@ -1,57 +1,138 @@
 use crate::parse::*;
-use nom::IResult;
 
+/// A name, referring to a variable or a function, or `module::name` for a
+/// function of another file. Calls are parsed by the postfix layer of
+/// expressions.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Literal {
+    /// The whole name, `math::gcd` for a path.
     pub ident: Ident,
-    pub call_arguments: Option<Tuple>,
+    /// The `<u64>` in `max::<u64>`, picking the instance of a generic function.
+    pub type_arguments: Vec<Type>,
+    pub span: Span,
+}
+
+impl Literal {
+    pub fn new(ident: Ident) -> Literal {
+        Literal {
+            span: ident.span,
+            ident,
+            type_arguments: Vec::new(),
+        }
+    }
 }
 
 impl Parse for Literal {
-    fn parse(input: &str) -> IResult<&str, Self> {
-        use nom::combinator::opt;
-        let (rest, ident) = Ident::parse(input)?;
+    fn parse(input: Input) -> ParseResult<Self> {
+        use util::token;
 
-        let (rest, call_arguments) = opt(Tuple::parse_ws)(rest)?;
+        let (mut rest, mut ident) = Ident::parse(input)?;
+        let mut type_arguments = Vec::new();
+
+        // `::name` continues the path, `::<types>` ends it.
+        while let Ok((after, _)) = token(TokenKind::ColonColon)(rest) {
+            match after.peek().kind {
+                TokenKind::Ident => {
+                    let (after, segment) = Ident::parse(after)?;
+                    ident.name = format!("{}::{}", ident.name, segment.name);
+                    ident.span = ident.span.to(segment.span);
+                    rest = after;
+                }
+                TokenKind::Lt => {
+                    let (after, types) = type_list(after)?;
+                    type_arguments = types;
+                    rest = after;
+                    break;
+                }
+                _ => {
+                    let expected = vec![
+                        Expected::Token(TokenKind::Ident),
+                        Expected::Token(TokenKind::Lt),
+                    ];
+                    let mut e = Error::new(after, expected);
+                    e.context = Some("after `::`");
+                    return Err(nom::Err::Failure(e));
+                }
+            }
+        }
 
         Ok((
             rest,
             Literal {
                 ident,
-                call_arguments,
+                type_arguments,
+                span: input.span_to(&rest),
             },
         ))
     }
 }
 
+/// `<a, b>`
+fn type_list(input: Input) -> ParseResult<Vec<Type>> {
+    use nom::{combinator::cut, multi::separated_nonempty_list, sequence::delimited};
+    use util::token;
+
+    delimited(
+        token(TokenKind::Lt),
+        cut(separated_nonempty_list(
+            token(TokenKind::Comma),
+            Type::parse,
+        )),
+        cut(token(TokenKind::Gt)),
+    )(input)
+}
+
 #[cfg(test)]
 mod literal_tests {
     use super::*;
     #[test]
     fn variable() {
         assert_eq!(
-            Literal::parse("hannover"),
-            Ok((
-                "",
-                Literal {
-                    ident: Ident("hannover".to_string()),
-                    call_arguments: None
-                }
-            ))
+            Literal::parse_str("hannover"),
+            Ok(("", Literal::new(Ident::new("hannover"))))
         );
     }
 
     #[test]
     fn function() {
         assert_eq!(
-            Literal::parse("leibniz()"),
+            Literal::parse_str("leibniz()"),
+            Ok(("()", Literal::new(Ident::new("leibniz"))))
+        );
+    }
+
+    #[test]
+    fn paths() {
+        let src = "math::gcd::<u64>(a, b)";
+        let (rest, literal) = Literal::parse_str(src).unwrap();
+        assert_eq!(rest, "(a, b)");
+        assert_eq!(literal.ident.name, "math::gcd");
+        assert_eq!(literal.ident.span.text(src), "math::gcd");
+        assert_eq!(literal.type_arguments, vec![Type::named("u64")]);
+        assert_eq!(literal.span.text(src), "math::gcd::<u64>");
+    }
+
+    #[test]
+    fn type_arguments() {
+        assert_eq!(
+            Literal::parse_str("max::<u64, [u8]>(a, b)"),
             Ok((
-                "",
+                "(a, b)",
                 Literal {
-                    ident: Ident("leibniz".to_string()),
-                    call_arguments: Some(Tuple::new())
+                    ident: Ident::new("max"),
+                    type_arguments: vec![Type::named("u64"), Type::parse_str("[u8]").unwrap().1],
+                    span: Span::default(),
                 }
             ))
         );
+        match Literal::parse_str("max::(a, b)") {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(
+                    e.to_string(),
+                    "expected identifier or `<` after `::`, found `(`"
+                )
+            }
+            res => panic!("expected a failure, got {:?}", res),
+        }
     }
 }
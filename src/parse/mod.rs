@@ -1,44 +1,66 @@
+mod error;
 pub mod expression;
+mod input;
 mod keyword;
+mod lexer;
 mod literal;
+mod pattern;
+mod span;
+mod token;
 mod tuple;
+mod types;
 mod util;
 mod value;
-pub use expression::Expression;
+pub use error::{Error, Expected};
+pub use expression::{BinaryOp, Expression, ExpressionKind, MatchArm, UnaryOp};
+pub use input::Input;
+pub use lexer::{lex, lex_at};
 pub use literal::Literal;
 use nom::IResult;
+pub use pattern::{Pattern, PatternKind};
+pub use span::{LineCol, Span};
+pub use token::{Token, TokenKind, Trivia, TriviaKind};
 pub use tuple::*;
-pub use value::Value;
+pub use types::{Type, TypeKind};
+pub use value::{Suffix, Value};
+
+pub type ParseResult<'a, T> = IResult<Input<'a>, T, Error>;
 
 pub trait Parse
 where
     Self: Sized,
 {
-    fn parse(input: &str) -> IResult<&str, Self>;
+    fn parse(input: Input) -> ParseResult<Self>;
 
-    fn parse_ws(input: &str) -> IResult<&str, Self> {
-        util::skip_whitespace(Self::parse)(input)
+    /// Parses `Self` from the start of `src`, handing back the unparsed rest.
+    fn parse_str(src: &str) -> IResult<&str, Self, Error> {
+        util::parse_str(Self::parse, src)
     }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Ident(pub String);
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+impl Ident {
+    pub fn new(name: impl Into<String>) -> Ident {
+        Ident {
+            name: name.into(),
+            span: Span::default(),
+        }
+    }
+}
 
 impl Parse for Ident {
-    fn parse(i: &str) -> IResult<&str, Ident> {
-        use nom::bytes::complete::take_while;
-        use nom::character::complete::alpha1;
-        use nom::character::is_alphanumeric;
+    fn parse(i: Input) -> ParseResult<Ident> {
         use nom::combinator::map;
-        use nom::sequence::pair;
 
-        map(
-            pair(
-                alpha1,
-                take_while(|c: char| is_alphanumeric(c as u8) || c == '_'),
-            ),
-            |(a, b)| Ident(format!("{}{}", a, b)),
-        )(i)
+        map(util::token(TokenKind::Ident), |t| Ident {
+            name: t.name(),
+            span: t.span,
+        })(i)
     }
 }
 
@@ -50,114 +72,557 @@ mod ident_tests {
     #[test]
     fn simple() {
         assert_eq!(
-            Ident::parse("hello_world12345"),
-            Ok(("", Ident("hello_world12345".to_string())))
+            Ident::parse_str("hello_world12345"),
+            Ok(("", Ident::new("hello_world12345")))
         )
     }
+
+    #[test]
+    fn span() {
+        let (_, ident) = Ident::parse_str("  abc ").unwrap();
+        assert_eq!((ident.span.start, ident.span.end), (2, 5));
+    }
+
+    #[test]
+    fn normalised() {
+        // `ü` as one code point and as `u` with a combining diaeresis.
+        let (_, composed) = Ident::parse_str("gr\u{fc}n").unwrap();
+        let (_, decomposed) = Ident::parse_str("gru\u{308}n").unwrap();
+        assert_eq!(composed.name, decomposed.name);
+        assert_eq!(decomposed.span.end, "gru\u{308}n".len());
+    }
+
+    #[test]
+    fn reserved() {
+        assert_eq!(Ident::parse_str("letter"), Ok(("", Ident::new("letter"))));
+        match Ident::parse_str("while") {
+            Err(nom::Err::Error(e)) => {
+                assert_eq!(e.to_string(), "expected identifier, found keyword `while`")
+            }
+            res => panic!("expected an error, got {:?}", res),
+        }
+    }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Type(pub String);
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ast {
+    pub items: Vec<Item>,
+    pub span: Span,
+}
 
-impl Parse for Type {
-    fn parse(i: &str) -> IResult<&str, Type> {
-        use nom::bytes::complete::take_while;
-        use nom::character::complete::alpha1;
-        use nom::character::is_alphanumeric;
-        use nom::combinator::map;
-        use nom::sequence::pair;
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Item {
+    Function(Function),
+    Struct(Struct),
+    Enum(Enum),
+    Import(Import),
+    Use(Use),
+    Const(Const),
+    Global(Global),
+    /// Something that should have been an item but failed to parse.
+    Error(Error),
+}
 
-        map(
-            pair(
-                alpha1,
-                take_while(|c: char| is_alphanumeric(c as u8) || c == '_'),
-            ),
-            |(a, b)| Type(format!("{}{}", a, b)),
-        )(i)
+/// Parses items up to the end of the input. Items that fail to parse become
+/// `Item::Error`s and parsing resumes at the next item, so this never
+/// fails itself.
+impl Parse for Ast {
+    fn parse(input: Input) -> ParseResult<Self> {
+        let start = input;
+        let mut input = input;
+        let mut items = Vec::new();
+
+        while input.peek().kind != TokenKind::Eof {
+            match Item::parse(input) {
+                Ok((rest, item)) => {
+                    items.push(item);
+                    input = rest;
+                }
+                Err(nom::Err::Error(mut e)) | Err(nom::Err::Failure(mut e)) => {
+                    if e.is_at(input) {
+                        e.expected = keyword::ITEM
+                            .iter()
+                            .chain(&[TokenKind::Eof])
+                            .map(|kind| Expected::Token(*kind))
+                            .collect();
+                    }
+                    input = util::recover(input, &e, &[], false);
+                    items.push(Item::Error(e));
+                }
+                Err(nom::Err::Incomplete(n)) => return Err(nom::Err::Incomplete(n)),
+            }
+        }
+
+        Ok((
+            input,
+            Ast {
+                items,
+                span: start.span_to(&input),
+            },
+        ))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl Ast {
+    /// Parses a whole source file, reporting every syntax error in it.
+    /// `Ast::parse` gives access to the tree even if there were errors.
+    pub fn parse_source(src: &str) -> Result<Ast, Vec<Error>> {
+        Ast::parse_source_at(src, 0)
+    }
 
-    #[test]
-    fn fibonacci_sample() {
-        let input = include_str!("../../sample-code/fibonacci.abc");
-        let result = Ast::parse_ws(input);
+    /// Like `parse_source`, with spans starting at `start`, see `lex_at`.
+    pub fn parse_source_at(src: &str, start: usize) -> Result<Ast, Vec<Error>> {
+        let tokens = lex_at(src, start);
+        let (_, ast) = Ast::parse(Input::new(&tokens)).expect("the top level recovers from errors");
+        let errors = ast.errors();
 
-        assert!(result.is_ok());
+        if errors.is_empty() {
+            Ok(ast)
+        } else {
+            Err(errors)
+        }
+    }
 
-        assert_eq!(result.unwrap().0, "\n");
+    pub fn functions(&self) -> impl Iterator<Item = &Function> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Function(function) => Some(function),
+            _ => None,
+        })
     }
 
-    #[test]
-    fn function_simple() {
-        assert_eq!(
-            Function::parse("function hello() {}"),
-            Ok((
-                "",
-                Function {
-                    name: Ident("hello".to_string()),
-                    args: Vec::new(),
-                    return_type: None,
-                    body: Body {
-                        statements: Vec::new()
-                    },
+    pub fn structs(&self) -> impl Iterator<Item = &Struct> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Struct(s) => Some(s),
+            _ => None,
+        })
+    }
+
+    pub fn enums(&self) -> impl Iterator<Item = &Enum> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Enum(e) => Some(e),
+            _ => None,
+        })
+    }
+
+    pub fn constants(&self) -> impl Iterator<Item = &Const> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Const(c) => Some(c),
+            _ => None,
+        })
+    }
+
+    pub fn globals(&self) -> impl Iterator<Item = &Global> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Global(g) => Some(g),
+            _ => None,
+        })
+    }
+
+    /// Every syntax error recorded in the tree, in source order.
+    pub fn errors(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for item in &self.items {
+            match item {
+                Item::Function(function) => function.body.collect_errors(&mut errors),
+                Item::Const(Const { value, .. }) | Item::Global(Global { value, .. }) => {
+                    value.collect_errors(&mut errors)
                 }
-            ))
-        )
+                Item::Struct(_) | Item::Enum(_) | Item::Import(_) | Item::Use(_) => {}
+                Item::Error(e) => errors.push(e.clone()),
+            }
+        }
+        // An unclosed block is reported by every block it is nested in.
+        errors.dedup_by(|a, b| a.span.start == b.span.start && a.expected == b.expected);
+        errors
+    }
+}
+
+impl Parse for Item {
+    fn parse(input: Input) -> ParseResult<Self> {
+        use nom::{branch::alt, combinator::map};
+
+        alt((
+            map(Function::parse, Item::Function),
+            map(Struct::parse, Item::Struct),
+            map(Enum::parse, Item::Enum),
+            map(Import::parse, Item::Import),
+            map(Use::parse, Item::Use),
+            map(Const::parse, Item::Const),
+            map(Global::parse, Item::Global),
+        ))(input)
     }
 }
 
+/// `name: Type`, as in parameter lists and struct declarations.
+/// `after_name` describes where a missing `:` is expected.
+fn typed_ident<'a>(
+    after_name: &'static str,
+) -> impl Fn(Input<'a>) -> ParseResult<'a, (Ident, Type)> {
+    use nom::{combinator::cut, error::context, sequence::separated_pair};
+
+    separated_pair(
+        Ident::parse,
+        cut(context(after_name, util::token(TokenKind::Colon))),
+        cut(Type::parse),
+    )
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct Ast {
-    pub functions: Vec<Function>,
+pub struct Struct {
+    /// The `///` comments in front of the struct, without the slashes.
+    pub doc: Option<String>,
+    pub name: Ident,
+    pub fields: Vec<(Ident, Type)>,
+    pub span: Span,
 }
 
-impl Parse for Ast {
-    fn parse(input: &str) -> IResult<&str, Self> {
-        use nom::{combinator::map, multi::many0};
+impl Parse for Struct {
+    fn parse(input: Input) -> ParseResult<Self> {
+        use nom::{
+            combinator::{cut, opt},
+            error::context,
+            multi::separated_list,
+            sequence::{delimited, preceded, terminated},
+        };
+        use util::token;
+
+        let doc = input.peek().doc();
+        let (rest, name) = preceded(
+            keyword::Struct::parse,
+            cut(context("after `struct`", Ident::parse)),
+        )(input)?;
+        let (rest, fields) = delimited(
+            cut(context("after struct name", token(TokenKind::LBrace))),
+            terminated(
+                separated_list(token(TokenKind::Comma), typed_ident("after field name")),
+                opt(token(TokenKind::Comma)),
+            ),
+            cut(token(TokenKind::RBrace)),
+        )(rest)?;
+
+        Ok((
+            rest,
+            Struct {
+                doc,
+                name,
+                fields,
+                span: input.span_to(&rest),
+            },
+        ))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Enum {
+    /// The `///` comments in front of the enum, without the slashes.
+    pub doc: Option<String>,
+    pub name: Ident,
+    pub variants: Vec<Variant>,
+    pub span: Span,
+}
+
+/// `Name` or `Name(Type, ..)`, one of the alternatives of an enum.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Variant {
+    pub name: Ident,
+    pub fields: Vec<Type>,
+    pub span: Span,
+}
+
+impl Enum {
+    pub fn variant(&self, name: &str) -> Option<&Variant> {
+        self.variants.iter().find(|v| v.name.name == name)
+    }
+}
+
+impl Parse for Enum {
+    fn parse(input: Input) -> ParseResult<Self> {
+        use nom::{
+            combinator::{cut, opt},
+            error::context,
+            multi::separated_list,
+            sequence::{delimited, preceded, terminated},
+        };
+        use util::token;
+
+        let doc = input.peek().doc();
+        let (rest, name) = preceded(
+            keyword::Enum::parse,
+            cut(context("after `enum`", Ident::parse)),
+        )(input)?;
+        let (rest, variants) = delimited(
+            cut(context("after enum name", token(TokenKind::LBrace))),
+            terminated(
+                separated_list(token(TokenKind::Comma), Variant::parse),
+                opt(token(TokenKind::Comma)),
+            ),
+            cut(token(TokenKind::RBrace)),
+        )(rest)?;
+
+        Ok((
+            rest,
+            Enum {
+                doc,
+                name,
+                variants,
+                span: input.span_to(&rest),
+            },
+        ))
+    }
+}
+
+impl Parse for Variant {
+    fn parse(input: Input) -> ParseResult<Self> {
+        use nom::{combinator::opt, multi::separated_list, sequence::pair};
+
+        let (rest, (name, fields)) = pair(
+            Ident::parse,
+            opt(util::delimited_paren(separated_list(
+                util::token(TokenKind::Comma),
+                Type::parse,
+            ))),
+        )(input)?;
+
+        Ok((
+            rest,
+            Variant {
+                name,
+                fields: fields.unwrap_or_default(),
+                span: input.span_to(&rest),
+            },
+        ))
+    }
+}
+
+/// `import "math.abc"`, making the functions of that file available as
+/// `math::name`. The path is relative to the importing file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Import {
+    pub path: String,
+    pub span: Span,
+}
+
+impl Parse for Import {
+    fn parse(input: Input) -> ParseResult<Self> {
+        use nom::{combinator::cut, error::context, sequence::preceded};
+
+        let (rest, path) = preceded(
+            keyword::Import::parse,
+            cut(context("after `import`", import_path)),
+        )(input)?;
+        Ok((
+            rest,
+            Import {
+                path,
+                span: input.span_to(&rest),
+            },
+        ))
+    }
+}
+
+fn import_path(input: Input) -> ParseResult<String> {
+    match Value::parse(input) {
+        Ok((rest, Value::String(path))) => Ok((rest, path)),
+        Ok(_) | Err(nom::Err::Error(_)) => Err(nom::Err::Error(Error::new(
+            input,
+            vec![Expected::Token(TokenKind::Str)],
+        ))),
+        Err(e) => Err(e),
+    }
+}
+
+/// `use math::gcd`, importing `math.abc` like `import "math.abc"` and
+/// making `gcd` available without the module name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Use {
+    pub module: Ident,
+    pub name: Ident,
+    pub span: Span,
+}
+
+impl Parse for Use {
+    fn parse(input: Input) -> ParseResult<Self> {
+        use nom::{
+            combinator::cut,
+            error::context,
+            sequence::{preceded, separated_pair},
+        };
+        use util::token;
+
+        let (rest, (module, name)) = preceded(
+            keyword::Use::parse,
+            cut(context(
+                "after `use`",
+                separated_pair(
+                    Ident::parse,
+                    cut(token(TokenKind::ColonColon)),
+                    cut(Ident::parse),
+                ),
+            )),
+        )(input)?;
+        Ok((
+            rest,
+            Use {
+                module,
+                name,
+                span: input.span_to(&rest),
+            },
+        ))
+    }
+}
+
+/// `const NAME: Type = value`, a value computed at compile time from
+/// literals and other constants.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Const {
+    /// The `///` comments in front of the constant, without the slashes.
+    pub doc: Option<String>,
+    pub name: Ident,
+    pub ty: Type,
+    pub value: Expression,
+    pub span: Span,
+}
+
+impl Parse for Const {
+    fn parse(input: Input) -> ParseResult<Self> {
+        let doc = input.peek().doc();
+        let (rest, (name, ty, value)) = declaration(
+            keyword::Const::parse,
+            "after `const`",
+            "after constant name",
+        )(input)?;
+
+        Ok((
+            rest,
+            Const {
+                doc,
+                name,
+                ty,
+                value,
+                span: input.span_to(&rest),
+            },
+        ))
+    }
+}
+
+/// `global name: Type = value`, a variable all functions share. Globals are
+/// set to their values in the order they are declared, before `main` runs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Global {
+    /// The `///` comments in front of the global, without the slashes.
+    pub doc: Option<String>,
+    pub name: Ident,
+    pub ty: Type,
+    pub value: Expression,
+    pub span: Span,
+}
+
+impl Parse for Global {
+    fn parse(input: Input) -> ParseResult<Self> {
+        let doc = input.peek().doc();
+        let (rest, (name, ty, value)) = declaration(
+            keyword::Global::parse,
+            "after `global`",
+            "after global name",
+        )(input)?;
+
+        Ok((
+            rest,
+            Global {
+                doc,
+                name,
+                ty,
+                value,
+                span: input.span_to(&rest),
+            },
+        ))
+    }
+}
+
+/// `keyword name: Type = value`, the shape of constants and globals.
+fn declaration<'a, K>(
+    keyword: impl Fn(Input<'a>) -> ParseResult<'a, K>,
+    after_keyword: &'static str,
+    after_name: &'static str,
+) -> impl Fn(Input<'a>) -> ParseResult<'a, (Ident, Type, Expression)> {
+    use nom::{combinator::cut, error::context, sequence::preceded};
 
-        map(many0(Function::parse_ws), |functions| Ast { functions })(input)
+    move |input| {
+        let (rest, (name, ty)) = preceded(
+            &keyword,
+            cut(context(after_keyword, typed_ident(after_name))),
+        )(input)?;
+        let (rest, value) = preceded(
+            cut(context("after type", util::token(TokenKind::Assign))),
+            cut(context("after `=`", Expression::parse)),
+        )(rest)?;
+        Ok((rest, (name, ty, value)))
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Function {
+    /// The `///` comments in front of the function, without the slashes.
+    pub doc: Option<String>,
     pub name: Ident,
+    /// The `T` in `function max<T>(a: T, b: T) -> T`.
+    pub type_params: Vec<Ident>,
     pub args: Vec<(Ident, Type)>,
     pub return_type: Option<Type>,
     pub body: Body,
+    pub span: Span,
 }
 
 impl Parse for Function {
-    fn parse(input: &str) -> IResult<&str, Self> {
-        use nom::combinator::opt;
-        use nom::multi::separated_list;
-        use nom::sequence::{preceded, separated_pair};
-        use util::{skip_whitespace, tag_ws};
-        let function_name_parser = preceded(keyword::Function::parse, Ident::parse_ws);
-        let identtype_parser = separated_pair(Ident::parse_ws, tag_ws(":"), Type::parse_ws);
-        let args_parser = skip_whitespace(util::delimited_paren(separated_list(
-            tag_ws(","),
-            identtype_parser,
-        )));
-        let body_parser = skip_whitespace(util::delimited_curly(Body::parse_ws));
-        let return_type_parser = opt(preceded(tag_ws("->"), Type::parse_ws));
-
-        let (input, name) = dbg!(function_name_parser(input))?;
-        let (input, args) = dbg!(args_parser(input))?;
-        let (input, return_type) = dbg!(return_type_parser(input))?;
-        let (input, body) = dbg!(body_parser(input))?;
+    fn parse(input: Input) -> ParseResult<Self> {
+        use nom::combinator::{cut, opt};
+        use nom::error::context;
+        use nom::multi::{separated_list, separated_nonempty_list};
+        use nom::sequence::{delimited, preceded};
+        use util::token;
+        let function_name_parser = preceded(
+            keyword::Function::parse,
+            cut(context("after `function`", Ident::parse)),
+        );
+        let type_params_parser = opt(delimited(
+            token(TokenKind::Lt),
+            cut(context(
+                "after `<`",
+                separated_nonempty_list(token(TokenKind::Comma), Ident::parse),
+            )),
+            cut(token(TokenKind::Gt)),
+        ));
+        let args_parser = cut(context(
+            "after function name",
+            util::delimited_paren(separated_list(
+                token(TokenKind::Comma),
+                typed_ident("after parameter name"),
+            )),
+        ));
+        let body_parser = cut(context("after function signature", Body::parse_block));
+        let return_type_parser = opt(preceded(
+            token(TokenKind::Arrow),
+            cut(context("after `->`", Type::parse)),
+        ));
+
+        let start = input;
+        let doc = input.peek().doc();
+        let (input, name) = function_name_parser(input)?;
+        let (input, type_params) = type_params_parser(input)?;
+        let (input, args) = args_parser(input)?;
+        let (input, return_type) = return_type_parser(input)?;
+        let (input, body) = body_parser(input)?;
 
         Ok((
             input,
             Function {
+                doc,
                 name,
+                type_params: type_params.unwrap_or_default(),
                 args,
                 return_type,
                 body,
+                span: start.span_to(&input),
             },
         ))
     }
@@ -166,13 +631,117 @@ impl Parse for Function {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Body {
     statements: Vec<Statement>,
+    pub span: Span,
 }
 
+/// Parses statements up to a closing `}`. A statement that fails to parse
+/// becomes a `StatementKind::Error` and parsing resumes at the next statement
+/// keyword or the end of the block.
 impl Parse for Body {
-    fn parse(input: &str) -> IResult<&str, Self> {
-        use nom::{combinator::map, multi::many0};
+    fn parse(input: Input) -> ParseResult<Self> {
+        let start = input;
+        let mut input = input;
+        let mut statements = Vec::new();
+
+        loop {
+            let statement_start = input;
+            match statement_start.peek().kind {
+                TokenKind::Eof | TokenKind::RBrace => break,
+                _ if keyword::starts_item(statement_start) => break,
+                _ => {}
+            }
+
+            match Statement::parse(statement_start) {
+                Ok((rest, statement)) => {
+                    statements.push(statement);
+                    input = rest;
+                }
+                Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                    input = util::recover(statement_start, &e, keyword::STATEMENT, true);
+                    statements.push(Statement {
+                        kind: StatementKind::Error(e),
+                        span: statement_start.span_to(&input),
+                    });
+                }
+                Err(nom::Err::Incomplete(n)) => return Err(nom::Err::Incomplete(n)),
+            }
+        }
+
+        Ok((
+            input,
+            Body {
+                statements,
+                span: start.span_to(&input),
+            },
+        ))
+    }
+}
+
+impl Body {
+    pub fn statements(&self) -> &[Statement] {
+        &self.statements
+    }
+
+    pub fn statements_mut(&mut self) -> &mut [Statement] {
+        &mut self.statements
+    }
+
+    /// The expression the body ends with, which is its value.
+    pub fn value(&self) -> Option<&Expression> {
+        match &self.statements.last()?.kind {
+            StatementKind::Expression(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Parses `{ statements }`. A missing `}` is recorded in the body
+    /// instead of failing, so that the statements before it are kept.
+    fn parse_block(input: Input) -> ParseResult<Body> {
+        let (rest, _) = util::token(TokenKind::LBrace)(input)?;
+        let (rest, mut body) = Body::parse(rest)?;
 
-        map(many0(Statement::parse_ws), |statements| Body { statements })(input)
+        match util::token(TokenKind::RBrace)(rest) {
+            Ok((rest, _)) => Ok((rest, body)),
+            Err(nom::Err::Error(e)) => {
+                body.statements.push(Statement {
+                    span: e.span,
+                    kind: StatementKind::Error(e),
+                });
+                Ok((rest, body))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn collect_errors(&self, errors: &mut Vec<Error>) {
+        for statement in &self.statements {
+            match &statement.kind {
+                StatementKind::Error(e) => errors.push(e.clone()),
+                StatementKind::While {
+                    condition, then, ..
+                } => {
+                    condition.collect_errors(errors);
+                    then.collect_errors(errors);
+                }
+                StatementKind::For {
+                    start, end, then, ..
+                } => {
+                    start.collect_errors(errors);
+                    end.collect_errors(errors);
+                    then.collect_errors(errors);
+                }
+                StatementKind::Let {
+                    assign: Some(e), ..
+                }
+                | StatementKind::Return(Some(e)) => e.collect_errors(errors),
+                StatementKind::Expression(e) => e.collect_errors(errors),
+                StatementKind::Assign { target, value, .. } => {
+                    target.collect_errors(errors);
+                    value.collect_errors(errors);
+                }
+                _ => {}
+            }
+        }
     }
 }
 
@@ -182,13 +751,15 @@ mod bodytests {
     #[test]
     fn simple_body() {
         assert_eq!(
-            Body::parse("doStuff()"),
+            Body::parse_str("doStuff()"),
             Ok((
                 "",
                 Body {
-                    statements: vec![Statement::Expression(Box::new(
-                        Expression::parse("doStuff()").unwrap().1
-                    ))]
+                    statements: vec![StatementKind::Expression(Box::new(
+                        Expression::parse_str("doStuff()").unwrap().1
+                    ))
+                    .into()],
+                    span: Span::default(),
                 }
             ))
         );
@@ -197,7 +768,7 @@ mod bodytests {
     #[test]
     fn complex_body() {
         assert_eq!(
-            Body::parse(
+            Body::parse_str(
                 "
             doStuff()
             doOtherStuff()"
@@ -206,11 +777,16 @@ mod bodytests {
                 "",
                 Body {
                     statements: vec![
-                        Statement::Expression(Box::new(Expression::parse("doStuff()").unwrap().1)),
-                        Statement::Expression(Box::new(
-                            Expression::parse("doOtherStuff()").unwrap().1
+                        StatementKind::Expression(Box::new(
+                            Expression::parse_str("doStuff()").unwrap().1
                         ))
-                    ]
+                        .into(),
+                        StatementKind::Expression(Box::new(
+                            Expression::parse_str("doOtherStuff()").unwrap().1
+                        ))
+                        .into()
+                    ],
+                    span: Span::default(),
                 }
             ))
         );
@@ -218,49 +794,194 @@ mod bodytests {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub enum Statement {
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StatementKind {
+    /// `let pattern: ty = assign`, where the pattern is a name, `_` or a
+    /// tuple of them, as in `let (q, r) = divmod(a, b)`.
     Let {
-        name: Ident,
+        pattern: Pattern,
+        ty: Option<Type>,
         assign: Option<Expression>,
     },
-    If {
+    While {
+        label: Option<Ident>,
         condition: Expression,
         then: Box<Body>,
-        otherwise: Option<Box<Body>>,
     },
-    While {
-        condition: Expression,
+    /// `for variable in start..end { }`, or `start..=end` to include `end`.
+    For {
+        label: Option<Ident>,
+        variable: Ident,
+        start: Expression,
+        end: Expression,
+        inclusive: bool,
         then: Box<Body>,
     },
+    /// `break`, or `break 'label` to leave an outer loop.
+    Break(Option<Ident>),
+    /// `continue`, or `continue 'label` to go on with an outer loop.
+    Continue(Option<Ident>),
+    /// `target = value`, or `target op= value` if `op` is set.
+    Assign {
+        target: Expression,
+        op: Option<BinaryOp>,
+        value: Expression,
+    },
     Return(Option<Expression>),
     Expression(Box<Expression>),
+    /// A statement that failed to parse.
+    Error(Error),
+}
+
+impl From<StatementKind> for Statement {
+    fn from(kind: StatementKind) -> Statement {
+        Statement {
+            kind,
+            span: Span::default(),
+        }
+    }
+}
+
+/// The assignment operators, with the operator they combine the old value with.
+const ASSIGN: &[(TokenKind, Option<BinaryOp>, &str)] = &[
+    (TokenKind::Assign, None, "after `=`"),
+    (TokenKind::PlusEq, Some(BinaryOp::Add), "after `+=`"),
+    (TokenKind::MinusEq, Some(BinaryOp::Subtract), "after `-=`"),
+    (TokenKind::StarEq, Some(BinaryOp::Multiply), "after `*=`"),
+    (TokenKind::SlashEq, Some(BinaryOp::Divide), "after `/=`"),
+];
+
+/// A loop label like `'outer`, kept with its `'`.
+fn label(i: Input) -> ParseResult<Ident> {
+    use nom::combinator::map;
+
+    map(util::token(TokenKind::Label), |t| Ident {
+        name: t.name(),
+        span: t.span,
+    })(i)
+}
+
+/// The label after `break` or `continue`. One followed by `:` belongs to a
+/// loop starting on the next line instead.
+fn jump_label(i: Input) -> ParseResult<Ident> {
+    use nom::{combinator::not, sequence::terminated};
+
+    terminated(label, not(util::token(TokenKind::Colon)))(i)
 }
 
 impl Statement {
-    fn parse_if(i: &str) -> IResult<&str, Statement> {
+    fn parse_assign(i: Input) -> ParseResult<StatementKind> {
+        use nom::{combinator::cut, error::context, Slice};
+
+        let (rest, target) = expression::postfix(i)?;
+        let (op, ctx) = match ASSIGN.iter().find(|(t, _, _)| *t == rest.peek().kind) {
+            Some((_, op, ctx)) => (*op, *ctx),
+            None => {
+                let expected = ASSIGN.iter().map(|(t, _, _)| Expected::Token(*t));
+                return Err(nom::Err::Error(Error::new(rest, expected.collect())));
+            }
+        };
+        if target.place_root().is_none() {
+            return Err(nom::Err::Failure(Error {
+                span: target.span,
+                ..Error::new(i, vec![Expected::Place])
+            }));
+        }
+        let (rest, value) = cut(context(ctx, Expression::parse))(rest.slice(1..))?;
+
+        Ok((rest, StatementKind::Assign { target, op, value }))
+    }
+
+    /// A `while` or `for` loop, optionally labelled with `'label:`.
+    fn parse_loop(i: Input) -> ParseResult<StatementKind> {
+        use nom::{
+            branch::alt,
+            combinator::{cut, opt},
+            error::context,
+            sequence::terminated,
+        };
+
+        let (rest, label) = opt(terminated(
+            label,
+            cut(context("after label", util::token(TokenKind::Colon))),
+        ))(i)?;
+        let loops = alt((
+            Statement::parse_while(label.clone()),
+            Statement::parse_for(label.clone()),
+        ));
+
+        match label {
+            Some(_) => cut(context("after label", loops))(rest),
+            None => loops(rest),
+        }
+    }
+
+    fn parse_while<'a>(
+        label: Option<Ident>,
+    ) -> impl Fn(Input<'a>) -> ParseResult<'a, StatementKind> {
+        use nom::{combinator::cut, error::context, sequence::preceded};
+
+        move |i| {
+            let (rest, condition) = preceded(
+                keyword::While::parse,
+                cut(context("after `while`", expression::condition)),
+            )(i)?;
+            let (rest, then) = cut(context("after `while` condition", Body::parse_block))(rest)?;
+
+            Ok((
+                rest,
+                StatementKind::While {
+                    label: label.clone(),
+                    condition,
+                    then: Box::new(then),
+                },
+            ))
+        }
+    }
+
+    fn parse_for<'a>(label: Option<Ident>) -> impl Fn(Input<'a>) -> ParseResult<'a, StatementKind> {
         use nom::{
-            combinator::{map, opt},
+            branch::alt,
+            combinator::{cut, map},
+            error::context,
             sequence::preceded,
         };
-        use util::{delimited_curly, skip_whitespace};
-        let (rest, condition) = preceded(keyword::If::parse, Expression::parse_ws)(i)?;
-        let (rest, then) = skip_whitespace(map(delimited_curly(Body::parse_ws), Box::new))(rest)?;
-        let (rest, otherwise) = opt(map(
-            preceded(
-                keyword::Else::parse_ws,
-                skip_whitespace(delimited_curly(Body::parse_ws)),
-            ),
-            Box::new,
-        ))(rest)?;
+        use util::token;
 
-        Ok((
-            rest,
-            Statement::If {
-                condition,
-                then,
-                otherwise,
-            },
-        ))
+        move |i| {
+            let (rest, variable) = preceded(
+                keyword::For::parse,
+                cut(context("after `for`", Ident::parse)),
+            )(i)?;
+            let (rest, _) = cut(context("after loop variable", keyword::In::parse))(rest)?;
+            let (rest, start) = cut(context("after `in`", Expression::parse))(rest)?;
+            let (rest, (inclusive, ctx)) = cut(context(
+                "after range start",
+                alt((
+                    map(token(TokenKind::DotDot), |_| (false, "after `..`")),
+                    map(token(TokenKind::DotDotEq), |_| (true, "after `..=`")),
+                )),
+            ))(rest)?;
+            let (rest, end) = cut(context(ctx, Expression::parse))(rest)?;
+            let (rest, then) = cut(context("after range", Body::parse_block))(rest)?;
+
+            Ok((
+                rest,
+                StatementKind::For {
+                    label: label.clone(),
+                    variable,
+                    start,
+                    end,
+                    inclusive,
+                    then: Box::new(then),
+                },
+            ))
+        }
     }
 }
 
@@ -270,13 +991,15 @@ mod statement_tests {
     #[test]
     fn test_let() {
         assert_eq!(
-            Statement::parse_ws("let x"),
+            Statement::parse_str("let x"),
             Ok((
                 "",
-                Statement::Let {
-                    name: Ident("x".to_string()),
+                StatementKind::Let {
+                    pattern: PatternKind::Name(Ident::new("x")).into(),
+                    ty: None,
                     assign: None
                 }
+                .into()
             ))
         );
     }
@@ -284,95 +1007,811 @@ mod statement_tests {
     #[test]
     fn test_let_assign_expression() {
         assert_eq!(
-            Statement::parse_ws("let x = true"),
+            Statement::parse_str("let x = true"),
             Ok((
                 "",
-                Statement::Let {
-                    name: Ident("x".to_string()),
-                    assign: Some(Expression::Value(value::Value::Boolean(true)))
+                StatementKind::Let {
+                    pattern: PatternKind::Name(Ident::new("x")).into(),
+                    ty: None,
+                    assign: Some(ExpressionKind::Value(value::Value::Boolean(true)).into())
                 }
+                .into()
             ))
         );
     }
 
     #[test]
-    fn test_if() {
+    fn test_let_underscore_name() {
         assert_eq!(
-            Statement::parse_ws("if true { true }"),
+            Statement::parse_str("let _x = 1"),
             Ok((
                 "",
-                Statement::If {
-                    condition: Expression::parse("true").unwrap().1,
-                    then: Box::new(Body::parse("true").unwrap().1),
-                    otherwise: None,
+                StatementKind::Let {
+                    pattern: PatternKind::Name(Ident::new("_x")).into(),
+                    ty: None,
+                    assign: Some(ExpressionKind::Value(value::Value::Integer(1, None)).into())
                 }
+                .into()
             ))
         );
     }
 
     #[test]
-    fn test_if_else() {
+    fn test_let_type() {
+        let u64 = Type::named("u64");
+
         assert_eq!(
-            Statement::parse_ws("if true { true } else { false }"),
+            Statement::parse_str("let x: u64 = 5"),
             Ok((
                 "",
-                Statement::If {
-                    condition: Expression::parse("true").unwrap().1,
-                    then: Box::new(Body::parse("true").unwrap().1),
-                    otherwise: Some(Box::new(Body::parse("false").unwrap().1)),
+                StatementKind::Let {
+                    pattern: PatternKind::Name(Ident::new("x")).into(),
+                    ty: Some(u64.clone()),
+                    assign: Some(Expression::parse_str("5").unwrap().1)
                 }
+                .into()
             ))
         );
-    }
+        assert_eq!(
+            Statement::parse_str("let x: u64"),
+            Ok((
+                "",
+                StatementKind::Let {
+                    pattern: PatternKind::Name(Ident::new("x")).into(),
+                    ty: Some(u64),
+                    assign: None
+                }
+                .into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_let_tuple() {
+        assert_eq!(
+            Statement::parse_str("let (q, r): (u64, u64) = divmod(a, b)"),
+            Ok((
+                "",
+                StatementKind::Let {
+                    pattern: Pattern::parse_str("(q, r)").unwrap().1,
+                    ty: Some(Type::parse_str("(u64, u64)").unwrap().1),
+                    assign: Some(Expression::parse_str("divmod(a, b)").unwrap().1)
+                }
+                .into()
+            ))
+        );
+    }
+
+    /// `if condition { then } else { otherwise }` as a statement.
+    fn if_statement(condition: &str, then: &str, otherwise: Option<&str>) -> Statement {
+        let body = |src| Box::new(Body::parse_str(src).unwrap().1);
+        StatementKind::Expression(Box::new(
+            ExpressionKind::If {
+                condition: Box::new(Expression::parse_str(condition).unwrap().1),
+                then: body(then),
+                otherwise: otherwise.map(body),
+            }
+            .into(),
+        ))
+        .into()
+    }
+
+    #[test]
+    fn test_if() {
+        assert_eq!(
+            Statement::parse_str("if true { true }"),
+            Ok(("", if_statement("true", "true", None)))
+        );
+    }
+
+    #[test]
+    fn test_if_block_not_struct() {
+        assert_eq!(
+            Statement::parse_str("if x { y }"),
+            Ok(("", if_statement("x", "y", None)))
+        );
+    }
+
+    #[test]
+    fn test_if_else() {
+        assert_eq!(
+            Statement::parse_str("if true { true } else { false }"),
+            Ok(("", if_statement("true", "true", Some("false"))))
+        );
+    }
+
+    #[test]
+    fn block_statements_end_at_brace() {
+        let (_, body) = Body::parse_str("if x { y }\n-a\n{ a }\n(b)").unwrap();
+        let kinds: Vec<_> = body
+            .statements()
+            .iter()
+            .map(|s| match &s.kind {
+                StatementKind::Expression(e) => match e.kind {
+                    ExpressionKind::If { .. } => "if",
+                    ExpressionKind::Block(_) => "block",
+                    _ => "expression",
+                },
+                _ => "other",
+            })
+            .collect();
+        assert_eq!(kinds, vec!["if", "expression", "block", "expression"]);
+    }
+
+    #[test]
+    fn test_assign() {
+        assert_eq!(
+            Statement::parse_str("x = x + 1"),
+            Ok((
+                "",
+                StatementKind::Assign {
+                    target: Expression::parse_str("x").unwrap().1,
+                    op: None,
+                    value: Expression::parse_str("x + 1").unwrap().1,
+                }
+                .into()
+            ))
+        );
+        assert_eq!(
+            Statement::parse_str("total /= 2"),
+            Ok((
+                "",
+                StatementKind::Assign {
+                    target: Expression::parse_str("total").unwrap().1,
+                    op: Some(BinaryOp::Divide),
+                    value: Expression::parse_str("2").unwrap().1,
+                }
+                .into()
+            ))
+        );
+        assert_eq!(
+            Statement::parse_str("a[i + 1] -= a[i]"),
+            Ok((
+                "",
+                StatementKind::Assign {
+                    target: Expression::parse_str("a[i + 1]").unwrap().1,
+                    op: Some(BinaryOp::Subtract),
+                    value: Expression::parse_str("a[i]").unwrap().1,
+                }
+                .into()
+            ))
+        );
+        assert_eq!(
+            Statement::parse_str("p.x *= 2"),
+            Ok((
+                "",
+                StatementKind::Assign {
+                    target: Expression::parse_str("p.x").unwrap().1,
+                    op: Some(BinaryOp::Multiply),
+                    value: Expression::parse_str("2").unwrap().1,
+                }
+                .into()
+            ))
+        );
+        assert_eq!(
+            Statement::parse_str("x == 1").unwrap().1.kind,
+            StatementKind::Expression(Box::new(Expression::parse_str("x == 1").unwrap().1))
+        );
+    }
+
+    #[test]
+    fn keyword_prefixes() {
+        for src in &["letter", "returnValue", "iffy()", "whileLoop", "elsewhere"] {
+            assert!(
+                matches!(
+                    Statement::parse_str(src),
+                    Ok((
+                        "",
+                        Statement {
+                            kind: StatementKind::Expression(_),
+                            ..
+                        }
+                    ))
+                ),
+                "{} should be an expression",
+                src
+            );
+        }
+    }
 
     #[test]
     fn test_while() {
         assert_eq!(
-            Statement::parse_ws("while true { doStuff() }"),
+            Statement::parse_str("while true { doStuff() }"),
+            Ok((
+                "",
+                StatementKind::While {
+                    label: None,
+                    condition: Expression::parse_str("true").unwrap().1,
+                    then: Box::new(Body::parse_str("doStuff()").unwrap().1),
+                }
+                .into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_for() {
+        assert_eq!(
+            Statement::parse_str("for i in 0..n + 1 { doStuff(i) }"),
             Ok((
                 "",
-                Statement::While {
-                    condition: Expression::parse("true").unwrap().1,
-                    then: Box::new(Body::parse("doStuff()").unwrap().1),
+                StatementKind::For {
+                    label: None,
+                    variable: Ident::new("i"),
+                    start: Expression::parse_str("0").unwrap().1,
+                    end: Expression::parse_str("n + 1").unwrap().1,
+                    inclusive: false,
+                    then: Box::new(Body::parse_str("doStuff(i)").unwrap().1),
                 }
+                .into()
             ))
         );
+        assert!(matches!(
+            Statement::parse_str("for i in 1..=n {}").unwrap().1.kind,
+            StatementKind::For {
+                inclusive: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_labels() {
+        let src = "'outer: while true { break 'outer continue }";
+        let (_, statement) = Statement::parse_str(src).unwrap();
+        let (label, then) = match statement.kind {
+            StatementKind::While { label, then, .. } => (label, then),
+            kind => panic!("expected a while loop, got {:?}", kind),
+        };
+
+        assert_eq!(label, Some(Ident::new("'outer")));
+        assert_eq!(label.unwrap().span.text(src), "'outer");
+        assert_eq!(
+            then.statements(),
+            &[
+                StatementKind::Break(Some(Ident::new("'outer"))).into(),
+                StatementKind::Continue(None).into(),
+            ]
+        );
+        assert_eq!(statement.span.text(src), src);
     }
 }
 
 impl Parse for Statement {
-    fn parse(input: &str) -> IResult<&str, Self> {
+    fn parse(input: Input) -> ParseResult<Self> {
         use nom::{
             branch::alt,
-            combinator::{map, opt},
-            sequence::{pair, preceded},
+            combinator::{cut, map, opt},
+            error::context,
+            sequence::{preceded, tuple},
         };
-        use util::{delimited_curly, skip_whitespace, tag_ws};
+        use util::{expecting, spanned, token};
 
-        alt((
+        expecting(
+            Expected::Statement,
             map(
-                pair(
-                    preceded(keyword::Let::parse, Ident::parse_ws),
-                    opt(preceded(tag_ws("="), Expression::parse_ws)),
-                ),
-                |(name, assign)| Statement::Let { name, assign },
+                spanned(alt((
+                    map(
+                        tuple((
+                            preceded(
+                                keyword::Let::parse,
+                                cut(context("after `let`", Pattern::parse_binding)),
+                            ),
+                            opt(preceded(
+                                token(TokenKind::Colon),
+                                cut(context("after `:`", Type::parse)),
+                            )),
+                            opt(preceded(
+                                token(TokenKind::Assign),
+                                cut(context("after `=`", Expression::parse)),
+                            )),
+                        )),
+                        |(pattern, ty, assign)| StatementKind::Let {
+                            pattern,
+                            ty,
+                            assign,
+                        },
+                    ),
+                    Statement::parse_loop,
+                    map(
+                        preceded(keyword::Break::parse, opt(jump_label)),
+                        StatementKind::Break,
+                    ),
+                    map(
+                        preceded(keyword::Continue::parse, opt(jump_label)),
+                        StatementKind::Continue,
+                    ),
+                    // An `if` or a block starting a statement ends at its
+                    // `}`, so `(a, b) = ..` on the next line isn't a call.
+                    map(expression::block_like, |e| {
+                        StatementKind::Expression(Box::new(e))
+                    }),
+                    Statement::parse_assign,
+                    map(
+                        preceded(keyword::Return::parse, opt(Expression::parse)),
+                        StatementKind::Return,
+                    ),
+                    map(Expression::parse, |e| {
+                        StatementKind::Expression(Box::new(e))
+                    }),
+                ))),
+                |(kind, span)| Statement { kind, span },
             ),
-            map(
-                pair(
-                    preceded(keyword::While::parse, Expression::parse_ws),
-                    skip_whitespace(delimited_curly(Body::parse_ws)),
+        )(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fibonacci_sample() {
+        let input = include_str!("../../sample-code/fibonacci.abc");
+        let ast = Ast::parse_source(input).unwrap();
+
+        assert_eq!(ast.functions().count(), 2);
+    }
+
+    fn source_error(src: &str) -> (String, LineCol) {
+        let error = &Ast::parse_source(src).unwrap_err()[0];
+        (error.to_string(), error.location(src))
+    }
+
+    #[test]
+    fn missing_body() {
+        assert_eq!(
+            source_error("function main()\n    return 0\n}"),
+            (
+                "expected `{` after function signature, found `return`".to_string(),
+                LineCol { line: 2, column: 5 }
+            )
+        );
+    }
+
+    #[test]
+    fn missing_name() {
+        assert_eq!(
+            source_error("function (n: u64) {}").0,
+            "expected identifier after `function`, found `(`"
+        );
+    }
+
+    #[test]
+    fn keyword_as_name() {
+        assert_eq!(
+            source_error("function main() {\n    let while = 1\n}"),
+            (
+                "expected identifier after `let`, found keyword `while`".to_string(),
+                LineCol { line: 2, column: 9 }
+            )
+        );
+        assert_eq!(
+            source_error("function if() {}").0,
+            "expected identifier after `function`, found keyword `if`"
+        );
+    }
+
+    #[test]
+    fn assign_to_value() {
+        assert_eq!(
+            source_error("function main() {\n    f(x)[0] = 1\n}"),
+            (
+                "expected assignable expression, found `f`".to_string(),
+                LineCol { line: 2, column: 5 }
+            )
+        );
+    }
+
+    #[test]
+    fn missing_let_type() {
+        assert_eq!(
+            source_error("function main() {\n    let x: = 1\n}").0,
+            "expected type after `:`, found `=`"
+        );
+    }
+
+    #[test]
+    fn missing_assigned_value() {
+        assert_eq!(
+            source_error("function main() {\n    x *= \n}").0,
+            "expected expression after `*=`, found `}`"
+        );
+    }
+
+    #[test]
+    fn missing_condition() {
+        assert_eq!(
+            source_error("function main() {\n    if {}\n}").0,
+            "expected expression after `if`, found `{`"
+        );
+    }
+
+    #[test]
+    fn malformed_loops() {
+        assert_eq!(
+            source_error("function main() {\n    'a: if x {}\n}").0,
+            "expected `while` or `for` after label, found `if`"
+        );
+        assert_eq!(
+            source_error("function main() {\n    for i 0..n {}\n}").0,
+            "expected `in` after loop variable, found `0`"
+        );
+        assert_eq!(
+            source_error("function main() {\n    for i in 0 n {}\n}").0,
+            "expected `..` or `..=` after range start, found `n`"
+        );
+    }
+
+    #[test]
+    fn lambda_in_body() {
+        let src = "function main() {
+    let f = function(x: u64) { x }
+    let = 1
+    let g = |x: u64| -> u64 { return x }
+}";
+        let errors = Ast::parse_source(src).unwrap_err();
+        let errors: Vec<_> = errors.iter().map(|e| e.location(src)).collect();
+        assert_eq!(errors, vec![LineCol { line: 3, column: 9 }]);
+
+        assert_eq!(
+            source_error("function main() {\n    let f = |x: u64|\n}").0,
+            "expected expression after lambda parameters, found `}`"
+        );
+    }
+
+    #[test]
+    fn missing_parameter_type() {
+        assert_eq!(
+            source_error("function f(n u64) {}").0,
+            "expected `:` after parameter name, found `u64`"
+        );
+    }
+
+    #[test]
+    fn unclosed_call() {
+        assert_eq!(
+            source_error("function f() {\n    g(\n").0,
+            "expected `)`, found end of input"
+        );
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let src = "function main() {
+    let x = (1 + 2
+    doStuff()
+    let y = )
+    return x
+}
+
+function (n: u64) {}
+
+function other() {
+    return 1
+}
+";
+        let errors: Vec<_> = Ast::parse_source(src)
+            .unwrap_err()
+            .iter()
+            .map(|e| (e.to_string(), e.location(src)))
+            .collect();
+
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    "expected `)`, found `doStuff`".to_string(),
+                    LineCol { line: 3, column: 5 }
                 ),
-                |(condition, then)| Statement::While {
-                    condition,
-                    then: Box::new(then),
-                },
-            ),
-            Statement::parse_if,
-            map(
-                preceded(keyword::Return::parse, opt(Expression::parse_ws)),
-                Statement::Return,
-            ),
-            map(Expression::parse, |e| Statement::Expression(Box::new(e))),
-        ))(input)
+                (
+                    "expected expression after `=`, found `)`".to_string(),
+                    LineCol {
+                        line: 4,
+                        column: 13
+                    }
+                ),
+                (
+                    "expected identifier after `function`, found `(`".to_string(),
+                    LineCol {
+                        line: 8,
+                        column: 10
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_block() {
+        let src = "function main() {\n    if x {\n        foo()\n\nfunction other() {}";
+        let (_, ast) = Ast::parse_str(src).unwrap();
+
+        assert_eq!(ast.functions().count(), 2);
+        assert_eq!(
+            ast.errors()
+                .iter()
+                .map(|e| (e.to_string(), e.location(src)))
+                .collect::<Vec<_>>(),
+            vec![(
+                "expected `}`, found `function`".to_string(),
+                LineCol { line: 5, column: 1 }
+            )]
+        );
+    }
+
+    #[test]
+    fn error_nodes() {
+        let src = "function main() {
+    let x = )
+    return 0
+}
+)
+function other() {}";
+        let (_, ast) = Ast::parse_str(src).unwrap();
+
+        match ast.items.as_slice() {
+            [Item::Function(main), Item::Error(e), Item::Function(other)] => {
+                assert_eq!(other.name, Ident::new("other"));
+                assert_eq!(
+                    e.to_string(),
+                    "expected one of `function`, `struct`, `enum`, `import`, `use`, `const`, `global`, end of input, found `)`"
+                );
+
+                let statements = &main.body.statements;
+                assert_eq!(statements.len(), 2);
+                assert!(matches!(statements[0].kind, StatementKind::Error(_)));
+                assert_eq!(statements[0].span.text(src), "let x = )");
+                assert_eq!(
+                    statements[1].kind,
+                    StatementKind::Return(Some(Expression::parse_str("0").unwrap().1))
+                );
+            }
+            items => panic!("unexpected items {:?}", items),
+        }
+    }
+
+    #[test]
+    fn leftover_input() {
+        assert_eq!(
+            source_error("function main() {}\nmian() {}"),
+            (
+                "expected one of `function`, `struct`, `enum`, `import`, `use`, `const`, `global`, end of input, found `mian`"
+                    .to_string(),
+                LineCol { line: 2, column: 1 }
+            )
+        );
+    }
+
+    #[test]
+    fn missing_field_type() {
+        assert_eq!(
+            source_error("struct Point {\n    x: f64,\n    y\n}").0,
+            "expected `:` after field name, found `}`"
+        );
+    }
+
+    #[test]
+    fn structs() {
+        let src = "/// A point.
+struct Point { x: f64, y: f64, }
+
+function main() { let p = Point { x: 1.0, y: 2.0 } }";
+        let (_, ast) = Ast::parse_str(src).unwrap();
+        let point = ast.structs().next().unwrap();
+
+        assert_eq!(point.doc.as_deref(), Some("A point."));
+        assert_eq!(point.name, Ident::new("Point"));
+        assert_eq!(
+            point.fields,
+            vec![
+                (Ident::new("x"), Type::named("f64")),
+                (Ident::new("y"), Type::named("f64")),
+            ]
+        );
+        assert_eq!(point.span.text(src), "struct Point { x: f64, y: f64, }");
+        assert_eq!(ast.functions().count(), 1);
+    }
+
+    #[test]
+    fn enums() {
+        let src = "/// Maybe a number.
+enum Option { Some(u64), None }
+
+enum Ordering {
+    Less,
+    Equal,
+    Greater,
+}";
+        let (_, ast) = Ast::parse_str(src).unwrap();
+        let enums: Vec<_> = ast.enums().collect();
+
+        assert_eq!(enums[0].doc.as_deref(), Some("Maybe a number."));
+        assert_eq!(enums[0].span.text(src), "enum Option { Some(u64), None }");
+        assert_eq!(enums[0].variants[0].span.text(src), "Some(u64)");
+        assert_eq!(enums[0].variants[0].fields, vec![Type::named("u64")]);
+        assert_eq!(enums[0].variants[1].fields, vec![]);
+
+        let names: Vec<_> = enums[1].variants.iter().map(|v| &v.name.name[..]).collect();
+        assert_eq!(names, vec!["Less", "Equal", "Greater"]);
+    }
+
+    #[test]
+    fn error_in_match_arm() {
+        assert_eq!(
+            source_error("function main() {\n    match x {\n        _ => { let }\n    }\n}"),
+            (
+                "expected identifier after `let`, found `}`".to_string(),
+                LineCol {
+                    line: 3,
+                    column: 20
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn function_simple() {
+        assert_eq!(
+            Function::parse_str("function hello() {}"),
+            Ok((
+                "",
+                Function {
+                    doc: None,
+                    name: Ident::new("hello"),
+                    type_params: Vec::new(),
+                    args: Vec::new(),
+                    return_type: None,
+                    body: Body {
+                        statements: Vec::new(),
+                        span: Span::default(),
+                    },
+                    span: Span::default(),
+                }
+            ))
+        )
+    }
+
+    #[test]
+    fn type_params() {
+        let (_, function) =
+            Function::parse_str("function max<T, U>(a: T, b: U) -> T { return a }").unwrap();
+        let params: Vec<_> = function
+            .type_params
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(params, vec!["T", "U"]);
+
+        assert_eq!(
+            source_error("function max<>() {}"),
+            (
+                "expected identifier after `<`, found `>`".to_string(),
+                LineCol {
+                    line: 1,
+                    column: 14
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn imports() {
+        let src = "import \"lib/math.abc\"\nuse util::swap\nfunction main() {}";
+        let (_, ast) = Ast::parse_str(src).unwrap();
+        match &ast.items[..] {
+            [Item::Import(import), Item::Use(u), Item::Function(_)] => {
+                assert_eq!(import.path, "lib/math.abc");
+                assert_eq!(import.span.text(src), "import \"lib/math.abc\"");
+                assert_eq!(
+                    (u.module.name.as_str(), u.name.name.as_str()),
+                    ("util", "swap")
+                );
+            }
+            items => panic!("expected an import, a use and a function, got {:?}", items),
+        }
+
+        assert_eq!(
+            source_error("import math"),
+            (
+                "expected string after `import`, found `math`".to_string(),
+                LineCol { line: 1, column: 8 }
+            )
+        );
+        assert_eq!(
+            source_error("use math"),
+            (
+                "expected `::`, found end of input".to_string(),
+                LineCol { line: 1, column: 9 }
+            )
+        );
+    }
+
+    #[test]
+    fn constants_and_globals() {
+        let src = "/// Past this, fibonacci numbers overflow.
+const LIMIT: u64 = 92
+global calls: [u64; 2] = [0, LIMIT - 1]";
+        let (_, ast) = Ast::parse_str(src).unwrap();
+        let limit = ast.constants().next().unwrap();
+        assert_eq!(
+            limit.doc.as_deref(),
+            Some("Past this, fibonacci numbers overflow.")
+        );
+        assert_eq!(limit.name.name, "LIMIT");
+        assert_eq!(limit.ty, Type::named("u64"));
+        assert_eq!(limit.span.text(src), "const LIMIT: u64 = 92");
+        let calls = ast.globals().next().unwrap();
+        assert_eq!(calls.value.span.text(src), "[0, LIMIT - 1]");
+
+        assert_eq!(
+            source_error("const LIMIT = 92"),
+            (
+                "expected `:` after constant name, found `=`".to_string(),
+                LineCol {
+                    line: 1,
+                    column: 13
+                }
+            )
+        );
+        assert_eq!(
+            source_error("global counter: u64\nfunction main() {}"),
+            (
+                "expected `=` after type, found `function`".to_string(),
+                LineCol { line: 2, column: 1 }
+            )
+        );
+    }
+
+    #[test]
+    fn doc_comments() {
+        let src = "// not documentation
+/// Adds one.
+///
+///   Really.
+function inc(n: u64) -> u64 { /* TODO */ return n + 1 }
+
+/* /// commented out */
+function main() {}";
+        let (_, ast) = Ast::parse_str(src).unwrap();
+        let docs: Vec<_> = ast.functions().map(|f| f.doc.as_deref()).collect();
+
+        assert_eq!(docs, vec![Some("Adds one.\n\n  Really."), None]);
+    }
+
+    #[test]
+    fn unterminated_comment() {
+        assert_eq!(
+            source_error("function main() {\n    /* TODO\n}"),
+            (
+                "expected statement, found `/*`".to_string(),
+                LineCol { line: 2, column: 5 }
+            )
+        );
+    }
+
+    #[test]
+    fn function_spans() {
+        let src = "\nfunction hello(n: u64) {\n    return n\n}\n";
+        let (_, ast) = Ast::parse_str(src).unwrap();
+        let function = ast.functions().next().unwrap();
+
+        assert_eq!(
+            function.span.text(src),
+            "function hello(n: u64) {\n    return n\n}"
+        );
+        assert_eq!(
+            function.name.span.line_col(src),
+            LineCol {
+                line: 2,
+                column: 10
+            }
+        );
+        assert_eq!(function.args[0].1.span.text(src), "u64");
+
+        let statement = &function.body.statements[0];
+        assert_eq!(statement.span.text(src), "return n");
+        assert_eq!(statement.span.line_col(src), LineCol { line: 3, column: 5 });
     }
 }
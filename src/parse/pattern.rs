@@ -0,0 +1,165 @@
+use crate::parse::{
+    util::{self, spanned, token, Parenthesised},
+    Expected, Ident, Input, Parse, ParseResult, Span, TokenKind, Value,
+};
+use nom::{
+    branch::alt,
+    combinator::{map, opt},
+    multi::separated_list,
+    sequence::pair,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatternKind {
+    /// `_`, matches anything.
+    Wildcard,
+    /// `name`, a variant without payload if some enum has one by that
+    /// name, otherwise a binding that matches anything.
+    Name(Ident),
+    /// `Variant(a, b)`
+    Variant { name: Ident, fields: Vec<Pattern> },
+    /// `(a, b)`
+    Tuple(Vec<Pattern>),
+    /// A literal like `0` or `"none"`, matching only values equal to it.
+    Value(Value),
+}
+
+impl From<PatternKind> for Pattern {
+    fn from(kind: PatternKind) -> Pattern {
+        Pattern {
+            kind,
+            span: Span::default(),
+        }
+    }
+}
+
+impl Parse for Pattern {
+    fn parse(input: Input) -> ParseResult<Self> {
+        let wildcard = map(token(TokenKind::Underscore), |_| PatternKind::Wildcard);
+        let name = map(
+            pair(
+                Ident::parse,
+                opt(util::delimited_paren(separated_list(
+                    token(TokenKind::Comma),
+                    Pattern::parse,
+                ))),
+            ),
+            |(name, fields)| match fields {
+                Some(fields) => PatternKind::Variant { name, fields },
+                None => PatternKind::Name(name),
+            },
+        );
+
+        util::expecting(
+            Expected::Pattern,
+            map(
+                spanned(alt((
+                    wildcard,
+                    map(Value::parse, PatternKind::Value),
+                    name,
+                    tuple(Pattern::parse),
+                ))),
+                |(kind, span)| Pattern { kind, span },
+            ),
+        )(input)
+    }
+}
+
+impl Pattern {
+    /// The patterns a `let` takes: names, `_` and tuples of them.
+    pub fn parse_binding(input: Input) -> ParseResult<Pattern> {
+        let wildcard = map(token(TokenKind::Underscore), |_| PatternKind::Wildcard);
+        let name = map(Ident::parse, PatternKind::Name);
+
+        util::expecting(
+            Expected::Token(TokenKind::Ident),
+            map(
+                spanned(alt((name, wildcard, tuple(Pattern::parse_binding)))),
+                |(kind, span)| Pattern { kind, span },
+            ),
+        )(input)
+    }
+}
+
+/// `(a, b)`, where `(a)` is just `a`.
+fn tuple<'a>(
+    f: impl Fn(Input<'a>) -> ParseResult<'a, Pattern>,
+) -> impl Fn(Input<'a>) -> ParseResult<'a, PatternKind> {
+    map(util::parenthesised(f), |found| match found {
+        Parenthesised::One(pattern) => pattern.kind,
+        Parenthesised::Tuple(patterns) => PatternKind::Tuple(patterns),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(src: &str) -> PatternKind {
+        Pattern::parse_str(src).unwrap().1.kind
+    }
+
+    #[test]
+    fn patterns() {
+        assert_eq!(parse("_"), PatternKind::Wildcard);
+        assert_eq!(parse("x"), PatternKind::Name(Ident::new("x")));
+        assert_eq!(parse("'a'"), PatternKind::Value(Value::Char('a')));
+        assert_eq!(
+            parse("Pair(_, Some(0))"),
+            PatternKind::Variant {
+                name: Ident::new("Pair"),
+                fields: vec![
+                    PatternKind::Wildcard.into(),
+                    PatternKind::Variant {
+                        name: Ident::new("Some"),
+                        fields: vec![PatternKind::Value(Value::Integer(0, None)).into()],
+                    }
+                    .into(),
+                ],
+            }
+        );
+
+        assert_eq!(
+            parse("(a, (_, 'b'))"),
+            PatternKind::Tuple(vec![
+                PatternKind::Name(Ident::new("a")).into(),
+                PatternKind::Tuple(vec![
+                    PatternKind::Wildcard.into(),
+                    PatternKind::Value(Value::Char('b')).into(),
+                ])
+                .into(),
+            ])
+        );
+
+        let src = "Some(x) ";
+        assert_eq!(Pattern::parse_str(src).unwrap().1.span.text(src), "Some(x)");
+    }
+
+    #[test]
+    fn bindings() {
+        let binding = |src| util::parse_str(Pattern::parse_binding, src);
+
+        assert_eq!(binding("(q, (_, r))"), Pattern::parse_str("(q, (_, r))"));
+        assert_eq!(binding("Some(x)").unwrap().0, "(x)");
+        match binding("1") {
+            Err(nom::Err::Error(e)) => {
+                assert_eq!(e.to_string(), "expected identifier, found `1`")
+            }
+            res => panic!("expected an error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn not_a_pattern() {
+        match Pattern::parse_str("+") {
+            Err(nom::Err::Error(e)) => assert_eq!(e.to_string(), "expected pattern, found `+`"),
+            res => panic!("expected an error, got {:?}", res),
+        }
+    }
+}
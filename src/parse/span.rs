@@ -0,0 +1,102 @@
+use std::fmt;
+
+/// Byte offsets `start..end` into the source a node was parsed from.
+///
+/// Spans never take part in AST equality: two trees parsed from differently
+/// laid out source compare equal. Compare `start` and `end` directly when the
+/// location itself matters.
+#[derive(Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// Smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The text this span covers in `src`.
+    pub fn text<'a>(&self, src: &'a str) -> &'a str {
+        &src[self.start..self.end]
+    }
+
+    /// Line and column of the start of this span.
+    pub fn line_col(&self, src: &str) -> LineCol {
+        LineCol::of(src, self.start)
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _: &Span) -> bool {
+        true
+    }
+}
+
+impl Eq for Span {}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// 1-based line and column, columns counted in chars.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl LineCol {
+    pub fn of(src: &str, offset: usize) -> LineCol {
+        let before = &src[..offset];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+        LineCol {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl fmt::Display for LineCol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_col() {
+        let src = "ab\ncd\n\nüx";
+        assert_eq!(LineCol::of(src, 0), LineCol { line: 1, column: 1 });
+        assert_eq!(LineCol::of(src, 4), LineCol { line: 2, column: 2 });
+        assert_eq!(LineCol::of(src, 7), LineCol { line: 4, column: 1 });
+        assert_eq!(LineCol::of(src, 9), LineCol { line: 4, column: 2 });
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(Span::new(3, 5).text("ab\ncd"), "cd");
+    }
+}
@@ -0,0 +1,259 @@
+use crate::parse::Span;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Ident,
+    Integer,
+    Float,
+    /// `"..."`, escapes not yet resolved.
+    Str,
+    /// `r"..."` or `r#"..."#`, with any number of `#`.
+    RawStr,
+    /// `'...'`, escapes not yet resolved.
+    Char,
+    /// `'name`, naming a loop.
+    Label,
+
+    Function,
+    Fn,
+    Struct,
+    Enum,
+    Match,
+    If,
+    Else,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
+    Return,
+    Let,
+    Import,
+    Use,
+    Const,
+    Global,
+    True,
+    False,
+
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Comma,
+    ColonColon,
+    Colon,
+    Semicolon,
+    DotDotEq,
+    DotDot,
+    Dot,
+    Arrow,
+    FatArrow,
+    Underscore,
+    Assign,
+    PlusEq,
+    MinusEq,
+    StarEq,
+    SlashEq,
+    EqEq,
+    NotEq,
+    LtEq,
+    GtEq,
+    Lt,
+    Gt,
+    Bang,
+    Pipe,
+    Amp,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+
+    /// A character no token starts with.
+    Unknown,
+    /// A `/*` that is never closed, up to the end of the input.
+    UnterminatedComment,
+    /// A string that is never closed, up to the end of the input.
+    UnterminatedStr,
+    /// Marks the end of the input, carries the trivia at the end of the file.
+    Eof,
+}
+
+/// Every keyword of the language, by spelling.
+pub const KEYWORDS: &[(&str, TokenKind)] = &[
+    ("function", TokenKind::Function),
+    ("fn", TokenKind::Fn),
+    ("struct", TokenKind::Struct),
+    ("enum", TokenKind::Enum),
+    ("match", TokenKind::Match),
+    ("if", TokenKind::If),
+    ("else", TokenKind::Else),
+    ("while", TokenKind::While),
+    ("for", TokenKind::For),
+    ("in", TokenKind::In),
+    ("break", TokenKind::Break),
+    ("continue", TokenKind::Continue),
+    ("return", TokenKind::Return),
+    ("let", TokenKind::Let),
+    ("import", TokenKind::Import),
+    ("use", TokenKind::Use),
+    ("const", TokenKind::Const),
+    ("global", TokenKind::Global),
+    ("true", TokenKind::True),
+    ("false", TokenKind::False),
+];
+
+/// Every operator and delimiter, longer ones first so they win over their prefixes.
+pub const PUNCTUATION: &[(&str, TokenKind)] = &[
+    ("..=", TokenKind::DotDotEq),
+    ("->", TokenKind::Arrow),
+    ("=>", TokenKind::FatArrow),
+    ("==", TokenKind::EqEq),
+    ("!=", TokenKind::NotEq),
+    ("+=", TokenKind::PlusEq),
+    ("-=", TokenKind::MinusEq),
+    ("*=", TokenKind::StarEq),
+    ("/=", TokenKind::SlashEq),
+    ("<=", TokenKind::LtEq),
+    (">=", TokenKind::GtEq),
+    ("(", TokenKind::LParen),
+    (")", TokenKind::RParen),
+    ("{", TokenKind::LBrace),
+    ("}", TokenKind::RBrace),
+    ("[", TokenKind::LBracket),
+    ("]", TokenKind::RBracket),
+    (",", TokenKind::Comma),
+    ("::", TokenKind::ColonColon),
+    (":", TokenKind::Colon),
+    (";", TokenKind::Semicolon),
+    ("..", TokenKind::DotDot),
+    (".", TokenKind::Dot),
+    ("_", TokenKind::Underscore),
+    ("=", TokenKind::Assign),
+    ("<", TokenKind::Lt),
+    (">", TokenKind::Gt),
+    ("!", TokenKind::Bang),
+    ("|", TokenKind::Pipe),
+    ("&", TokenKind::Amp),
+    ("+", TokenKind::Plus),
+    ("-", TokenKind::Minus),
+    ("*", TokenKind::Star),
+    ("/", TokenKind::Slash),
+];
+
+impl TokenKind {
+    /// How a keyword or punctuation token is spelled, `None` for the others.
+    pub fn symbol(self) -> Option<&'static str> {
+        KEYWORDS
+            .iter()
+            .chain(PUNCTUATION)
+            .find(|(_, kind)| *kind == self)
+            .map(|(symbol, _)| *symbol)
+    }
+
+    /// The keyword spelled `word`, if it is one.
+    pub fn keyword(word: &str) -> Option<TokenKind> {
+        KEYWORDS
+            .iter()
+            .find(|(keyword, _)| *keyword == word)
+            .map(|(_, kind)| *kind)
+    }
+
+    pub fn is_keyword(self) -> bool {
+        KEYWORDS.iter().any(|(_, kind)| *kind == self)
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(symbol) = self.symbol() {
+            return write!(f, "`{}`", symbol);
+        }
+
+        match self {
+            TokenKind::Ident => write!(f, "identifier"),
+            TokenKind::Integer => write!(f, "integer"),
+            TokenKind::Float => write!(f, "float"),
+            TokenKind::Str | TokenKind::RawStr => write!(f, "string"),
+            TokenKind::Char => write!(f, "character"),
+            TokenKind::Label => write!(f, "label"),
+            TokenKind::Eof => write!(f, "end of input"),
+            TokenKind::UnterminatedComment => write!(f, "unterminated comment"),
+            TokenKind::UnterminatedStr => write!(f, "unterminated string"),
+            _ => write!(f, "unknown character"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    /// `// ...` up to the end of the line.
+    LineComment,
+    /// `/* ... */`, which may nest.
+    BlockComment,
+    /// `/// ...`, documenting the item that follows.
+    DocComment,
+}
+
+/// Source text between tokens that doesn't affect the meaning of the program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Trivia<'a> {
+    pub kind: TriviaKind,
+    pub text: &'a str,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub span: Span,
+    /// The trivia between the previous token and this one.
+    pub leading_trivia: Vec<Trivia<'a>>,
+}
+
+impl<'a> Token<'a> {
+    /// The name an identifier or label stands for: its text in Unicode
+    /// normal form C, so that `ü` written as one or as two code points
+    /// is the same name.
+    pub fn name(&self) -> String {
+        use unicode_normalization::UnicodeNormalization;
+
+        self.text.nfc().collect()
+    }
+
+    /// How the token is shown in an error, `None` for the end of input.
+    /// Unterminated tokens run to the end of the input, so only their
+    /// opening delimiter is shown.
+    pub fn found(&self) -> Option<&'a str> {
+        match self.kind {
+            TokenKind::Eof => None,
+            TokenKind::UnterminatedComment => Some("/*"),
+            TokenKind::UnterminatedStr => self.text.find('"').map(|i| &self.text[..=i]),
+            _ => Some(self.text),
+        }
+    }
+
+    /// The doc comments in front of this token, one line per comment,
+    /// or `None` if there are none.
+    pub fn doc(&self) -> Option<String> {
+        let lines: Vec<_> = self
+            .leading_trivia
+            .iter()
+            .filter(|t| t.kind == TriviaKind::DocComment)
+            .map(|t| {
+                let line = t.text.trim_start_matches("///");
+                line.strip_prefix(' ').unwrap_or(line).trim_end()
+            })
+            .collect();
+
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+}
@@ -1,25 +1,37 @@
 use crate::parse::{
-    util::{delimited_paren, tag_ws},
-    Expression, Parse,
+    util::{delimited_paren, spanned, token},
+    Expression, Input, Parse, ParseResult, Span, TokenKind,
 };
 
-use nom::{combinator::map, multi::separated_list};
+use nom::{
+    combinator::{cond, map, opt},
+    multi::separated_list,
+};
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct Tuple(Vec<Expression>);
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tuple {
+    pub items: Vec<Expression>,
+    pub span: Span,
+}
 
 impl Tuple {
     pub fn new() -> Tuple {
-        Tuple(Vec::new())
+        Tuple::default()
     }
 }
 
 impl Parse for Tuple {
-    fn parse(input: &str) -> nom::IResult<&str, Self> {
-        map(
-            delimited_paren(separated_list(tag_ws(","), Expression::parse_ws)),
-            Tuple,
-        )(input)
+    /// `(a, b, ..)`, allowing a trailing comma after the last item.
+    fn parse(input: Input) -> ParseResult<Self> {
+        let items = |input| {
+            let (rest, items) = separated_list(token(TokenKind::Comma), Expression::parse)(input)?;
+            let (rest, _) = cond(!items.is_empty(), opt(token(TokenKind::Comma)))(rest)?;
+            Ok((rest, items))
+        };
+        map(spanned(delimited_paren(items)), |(items, span)| Tuple {
+            items,
+            span,
+        })(input)
     }
 }
 
@@ -29,7 +41,17 @@ mod tests {
 
     #[test]
     fn empty_tuple() {
-        let res = Tuple::parse("()");
-        assert_eq!(res, Ok(("", Tuple(Vec::new()))));
+        let res = Tuple::parse_str("()");
+        assert_eq!(res, Ok(("", Tuple::new())));
+    }
+
+    #[test]
+    fn trailing_comma() {
+        let (rest, tuple) = Tuple::parse_str("(a,)").unwrap();
+        assert_eq!((rest, tuple.items.len()), ("", 1));
+        let (rest, tuple) = Tuple::parse_str("(a, b,)").unwrap();
+        assert_eq!((rest, tuple.items.len()), ("", 2));
+        assert!(Tuple::parse_str("(,)").is_err());
+        assert!(Tuple::parse_str("(a,,)").is_err());
     }
 }
@@ -0,0 +1,354 @@
+use crate::parse::{
+    keyword,
+    util::{
+        delimited_bracket, delimited_paren, expecting, parenthesised, spanned, token, Parenthesised,
+    },
+    Error, Expected, Input, Parse, ParseResult, Span, TokenKind, Value,
+};
+use nom::{branch::alt, combinator::map};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Type {
+    pub kind: TypeKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TypeKind {
+    /// A type referred to by name, like `u64`.
+    Named(String),
+    /// A generic type applied to type arguments, like `List<u64>`.
+    Generic { name: String, arguments: Vec<Type> },
+    /// `[element; len]`, or `[element]` if the length isn't fixed.
+    Array {
+        element: Box<Type>,
+        len: Option<u64>,
+    },
+    /// `(a, b)`, or `()` for no value at all.
+    Tuple(Vec<Type>),
+    /// `fn(params) -> result`, without a result for functions returning nothing.
+    Function {
+        params: Vec<Type>,
+        result: Option<Box<Type>>,
+    },
+    /// `&target`
+    Reference(Box<Type>),
+}
+
+impl Type {
+    pub fn named(name: impl Into<String>) -> Type {
+        TypeKind::Named(name.into()).into()
+    }
+}
+
+impl From<TypeKind> for Type {
+    fn from(kind: TypeKind) -> Type {
+        Type {
+            kind,
+            span: Span::default(),
+        }
+    }
+}
+
+/// Writes the type the way it is spelled in source.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn list(f: &mut fmt::Formatter, types: &[Type]) -> fmt::Result {
+            for (i, ty) in types.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", ty)?;
+            }
+            Ok(())
+        }
+
+        match &self.kind {
+            TypeKind::Named(name) => write!(f, "{}", name),
+            TypeKind::Generic { name, arguments } => {
+                write!(f, "{}<", name)?;
+                list(f, arguments)?;
+                write!(f, ">")
+            }
+            TypeKind::Array { element, len } => match len {
+                Some(len) => write!(f, "[{}; {}]", element, len),
+                None => write!(f, "[{}]", element),
+            },
+            TypeKind::Tuple(types) => {
+                write!(f, "(")?;
+                list(f, types)?;
+                if types.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            TypeKind::Function { params, result } => {
+                write!(f, "fn(")?;
+                list(f, params)?;
+                write!(f, ")")?;
+                match result {
+                    Some(result) => write!(f, " -> {}", result),
+                    None => Ok(()),
+                }
+            }
+            TypeKind::Reference(target) => write!(f, "&{}", target),
+        }
+    }
+}
+
+impl Parse for Type {
+    fn parse(i: Input) -> ParseResult<Type> {
+        expecting(
+            Expected::Type,
+            map(
+                spanned(alt((named, array, tuple, function, reference))),
+                |(kind, span)| Type { kind, span },
+            ),
+        )(i)
+    }
+}
+
+/// `Name`, or `Name<arguments>` for a generic type.
+fn named(i: Input) -> ParseResult<TypeKind> {
+    use nom::{
+        combinator::cut, error::context, multi::separated_nonempty_list, sequence::delimited,
+    };
+
+    let (rest, name) = token(TokenKind::Ident)(i)?;
+    let name = name.name();
+    if rest.peek().kind != TokenKind::Lt {
+        return Ok((rest, TypeKind::Named(name)));
+    }
+
+    let (rest, arguments) = delimited(
+        token(TokenKind::Lt),
+        cut(context(
+            "after `<`",
+            separated_nonempty_list(token(TokenKind::Comma), Type::parse),
+        )),
+        cut(token(TokenKind::Gt)),
+    )(rest)?;
+    Ok((rest, TypeKind::Generic { name, arguments }))
+}
+
+fn reference(i: Input) -> ParseResult<TypeKind> {
+    use nom::{combinator::cut, error::context, sequence::preceded};
+
+    map(
+        preceded(
+            token(TokenKind::Amp),
+            cut(context("after `&`", Type::parse)),
+        ),
+        |target| TypeKind::Reference(Box::new(target)),
+    )(i)
+}
+
+fn array(i: Input) -> ParseResult<TypeKind> {
+    use nom::{
+        combinator::{cut, opt},
+        error::context,
+        sequence::{pair, preceded},
+    };
+
+    map(
+        delimited_bracket(pair(
+            Type::parse,
+            opt(preceded(
+                token(TokenKind::Semicolon),
+                cut(context("after `;`", array_len)),
+            )),
+        )),
+        |(element, len)| TypeKind::Array {
+            element: Box::new(element),
+            len,
+        },
+    )(i)
+}
+
+fn tuple(i: Input) -> ParseResult<TypeKind> {
+    map(parenthesised(Type::parse), |found| match found {
+        Parenthesised::One(ty) => ty.kind,
+        Parenthesised::Tuple(types) => TypeKind::Tuple(types),
+    })(i)
+}
+
+fn function(i: Input) -> ParseResult<TypeKind> {
+    use nom::{
+        combinator::{cut, opt},
+        error::context,
+        multi::separated_list,
+        sequence::{pair, preceded},
+    };
+
+    map(
+        preceded(
+            keyword::Fn::parse,
+            pair(
+                cut(context(
+                    "after `fn`",
+                    delimited_paren(separated_list(token(TokenKind::Comma), Type::parse)),
+                )),
+                opt(preceded(
+                    token(TokenKind::Arrow),
+                    cut(context("after `->`", Type::parse)),
+                )),
+            ),
+        ),
+        |(params, result)| TypeKind::Function {
+            params,
+            result: result.map(Box::new),
+        },
+    )(i)
+}
+
+fn array_len(i: Input) -> ParseResult<u64> {
+    match Value::parse(i) {
+        Ok((rest, Value::Integer(len, _))) => Ok((rest, len)),
+        Ok(_) | Err(nom::Err::Error(_)) => Err(nom::Err::Error(Error::new(
+            i,
+            vec![Expected::Token(TokenKind::Integer)],
+        ))),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn array(element: Type, len: Option<u64>) -> Type {
+        TypeKind::Array {
+            element: Box::new(element),
+            len,
+        }
+        .into()
+    }
+
+    #[test]
+    fn named() {
+        assert_eq!(Type::parse_str("u64"), Ok(("", Type::named("u64"))));
+    }
+
+    #[test]
+    fn arrays() {
+        assert_eq!(
+            Type::parse_str("[u64; 10]"),
+            Ok(("", array(Type::named("u64"), Some(10))))
+        );
+        assert_eq!(
+            Type::parse_str("[[bool; 0x10]]"),
+            Ok(("", array(array(Type::named("bool"), Some(16)), None)))
+        );
+    }
+
+    #[test]
+    fn tuples() {
+        assert_eq!(
+            Type::parse_str("(u64, [bool])"),
+            Ok((
+                "",
+                TypeKind::Tuple(vec![Type::named("u64"), array(Type::named("bool"), None)]).into()
+            ))
+        );
+        assert_eq!(
+            Type::parse_str("()"),
+            Ok(("", TypeKind::Tuple(vec![]).into()))
+        );
+        assert_eq!(
+            Type::parse_str("(u64,)"),
+            Ok(("", TypeKind::Tuple(vec![Type::named("u64")]).into()))
+        );
+        assert_eq!(Type::parse_str("(u64)"), Ok(("", Type::named("u64"))));
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            Type::parse_str("fn(u64, [u8]) -> fn() -> bool"),
+            Ok((
+                "",
+                TypeKind::Function {
+                    params: vec![Type::named("u64"), array(Type::named("u8"), None)],
+                    result: Some(Box::new(
+                        TypeKind::Function {
+                            params: vec![],
+                            result: Some(Box::new(Type::named("bool"))),
+                        }
+                        .into()
+                    )),
+                }
+                .into()
+            ))
+        );
+        assert_eq!(
+            Type::parse_str("fn(u64)"),
+            Ok((
+                "",
+                TypeKind::Function {
+                    params: vec![Type::named("u64")],
+                    result: None,
+                }
+                .into()
+            ))
+        );
+    }
+
+    #[test]
+    fn generics() {
+        assert_eq!(
+            Type::parse_str("Map<u64, List<&str>>"),
+            Ok((
+                "",
+                TypeKind::Generic {
+                    name: "Map".to_string(),
+                    arguments: vec![
+                        Type::named("u64"),
+                        TypeKind::Generic {
+                            name: "List".to_string(),
+                            arguments: vec![
+                                TypeKind::Reference(Box::new(Type::named("str"))).into()
+                            ],
+                        }
+                        .into(),
+                    ],
+                }
+                .into()
+            ))
+        );
+
+        let src = "List<u64> ";
+        assert_eq!(Type::parse_str(src).unwrap().1.span.text(src), "List<u64>");
+
+        match Type::parse_str("List<>") {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(e.to_string(), "expected type after `<`, found `>`")
+            }
+            res => panic!("expected a failure, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn display() {
+        for src in &[
+            "u64",
+            "List<(u8,)>",
+            "[&Point; 3]",
+            "&[u64]",
+            "fn((), bool) -> Map<str, u64>",
+            "fn(fn(u64))",
+        ] {
+            assert_eq!(Type::parse_str(src).unwrap().1.to_string(), *src);
+        }
+    }
+
+    #[test]
+    fn invalid_length() {
+        match Type::parse_str("[u64; n]") {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(e.to_string(), "expected integer after `;`, found `n`")
+            }
+            res => panic!("expected a failure, got {:?}", res),
+        }
+    }
+}
@@ -1,48 +1,206 @@
-use nom::{
-    character::complete::char,
-    sequence::{delimited, preceded},
-    IResult,
-};
+use crate::parse::{keyword, lex, Error, Expected, Input, ParseResult, Span, Token, TokenKind};
+use nom::{combinator::cut, sequence::delimited, Slice};
 
-pub fn skip_whitespace<'a, F, T>(f: F) -> impl Fn(&'a str) -> IResult<&'a str, T>
-where
-    F: Fn(&'a str) -> IResult<&str, T>,
-{
-    use nom::character::complete::one_of;
-    use nom::multi::many0;
-    let skip = many0(one_of("\n\r\t "));
-    preceded(skip, f)
+/// Matches a single token of `kind`, reporting it as expected if it isn't there.
+pub fn token<'a>(kind: TokenKind) -> impl Fn(Input<'a>) -> ParseResult<'a, &'a Token<'a>> {
+    move |input: Input<'a>| {
+        let token = input.peek();
+        if token.kind == kind {
+            Ok((input.slice(1..), token))
+        } else {
+            Err(nom::Err::Error(Error::new(
+                input,
+                vec![Expected::Token(kind)],
+            )))
+        }
+    }
 }
 
-pub fn tag_ws<'a>(s: &'a str) -> impl Fn(&'a str) -> IResult<&'a str, &'a str> {
-    skip_whitespace(nom::bytes::complete::tag(s))
+/// Once the opening paren is there, the group has to be well formed.
+pub fn delimited_paren<'a, T>(
+    f: impl Fn(Input<'a>) -> ParseResult<'a, T>,
+) -> impl Fn(Input<'a>) -> ParseResult<'a, T> {
+    delimited(
+        token(TokenKind::LParen),
+        cut(f),
+        cut(token(TokenKind::RParen)),
+    )
 }
 
-pub fn delimited_curly<'a, T>(
-    f: impl Fn(&'a str) -> IResult<&'a str, T>,
-) -> impl Fn(&'a str) -> IResult<&'a str, T> {
-    delimited(char('{'), f, tag_ws("}"))
+/// What `parenthesised` found: `(a)` is just `a`, `()`, `(a,)` and
+/// `(a, b)` are tuples.
+#[derive(Debug, PartialEq)]
+pub enum Parenthesised<T> {
+    One(T),
+    Tuple(Vec<T>),
 }
 
-pub fn delimited_paren<'a, T>(
-    f: impl Fn(&'a str) -> IResult<&'a str, T>,
-) -> impl Fn(&'a str) -> IResult<&'a str, T> {
-    delimited(char('('), f, tag_ws(")"))
+/// `(a, b, ..)`, allowing a trailing comma after the last item.
+pub fn parenthesised<'a, T>(
+    f: impl Fn(Input<'a>) -> ParseResult<'a, T>,
+) -> impl Fn(Input<'a>) -> ParseResult<'a, Parenthesised<T>> {
+    use nom::{combinator::opt, multi::separated_list};
+
+    let items = move |input| {
+        let (rest, mut items) = separated_list(token(TokenKind::Comma), &f)(input)?;
+        if items.is_empty() {
+            return Ok((rest, Parenthesised::Tuple(items)));
+        }
+
+        match opt(token(TokenKind::Comma))(rest)? {
+            (rest, None) if items.len() == 1 => Ok((rest, Parenthesised::One(items.remove(0)))),
+            (rest, _) => Ok((rest, Parenthesised::Tuple(items))),
+        }
+    };
+    delimited_paren(items)
+}
+
+/// Like `delimited_paren`, with `[` and `]`.
+pub fn delimited_bracket<'a, T>(
+    f: impl Fn(Input<'a>) -> ParseResult<'a, T>,
+) -> impl Fn(Input<'a>) -> ParseResult<'a, T> {
+    delimited(
+        token(TokenKind::LBracket),
+        cut(f),
+        cut(token(TokenKind::RBracket)),
+    )
+}
+
+/// Replaces whatever `f` expected with `expected` when `f` fails without
+/// getting anywhere, e.g. "expected expression" rather than a list of
+/// every token an expression may start with.
+pub fn expecting<'a, T>(
+    expected: Expected,
+    f: impl Fn(Input<'a>) -> ParseResult<'a, T>,
+) -> impl Fn(Input<'a>) -> ParseResult<'a, T> {
+    move |input: Input<'a>| match f(input) {
+        Err(nom::Err::Error(e)) if e.is_at(input) => Err(nom::Err::Error(Error {
+            expected: vec![expected.clone()],
+            ..e
+        })),
+        result => result,
+    }
+}
+
+/// Runs `f` and pairs its output with the span of the tokens it consumed.
+pub fn spanned<'a, T>(
+    f: impl Fn(Input<'a>) -> ParseResult<'a, T>,
+) -> impl Fn(Input<'a>) -> ParseResult<'a, (T, Span)> {
+    move |input: Input<'a>| {
+        let (rest, t) = f(input)?;
+        Ok((rest, (t, input.span_to(&rest))))
+    }
+}
+
+/// After `error` broke the construct starting at `start`, skips ahead to
+/// where parsing can pick up again: the next item, one of `keywords`,
+/// or, if `closing_brace` is set, a `}` closing the enclosing block.
+/// Braces opened on the way are skipped as a whole.
+pub fn recover<'a>(
+    start: Input<'a>,
+    error: &Error,
+    keywords: &[TokenKind],
+    closing_brace: bool,
+) -> Input<'a> {
+    let skip = start
+        .tokens
+        .iter()
+        .position(|t| t.span.start >= error.span.start)
+        .unwrap_or(start.tokens.len() - 1);
+    let mut rest = start.slice(skip..);
+    // If the error is right at the start, step over at least one token
+    // so that the caller makes progress.
+    let mut skip_first = skip == 0;
+    let mut depth = 0;
+
+    loop {
+        let kind = rest.peek().kind;
+        if kind == TokenKind::Eof {
+            return rest;
+        }
+
+        if !skip_first {
+            if keyword::starts_item(rest) {
+                return rest;
+            }
+            if depth == 0
+                && (keywords.contains(&kind) || closing_brace && kind == TokenKind::RBrace)
+            {
+                return rest;
+            }
+        }
+
+        match kind {
+            TokenKind::LBrace => depth += 1,
+            TokenKind::RBrace if depth > 0 => depth -= 1,
+            _ => {}
+        }
+        skip_first = false;
+        rest = rest.slice(1..);
+    }
+}
+
+/// Lexes `s` and runs `f` on it, handing back the unparsed rest as a string.
+pub fn parse_str<T>(
+    f: impl for<'a> Fn(Input<'a>) -> ParseResult<'a, T>,
+    s: &str,
+) -> nom::IResult<&str, T, Error> {
+    let tokens = lex(s);
+    f(Input::new(&tokens)).map(|(rest, t)| (&s[rest.peek().span.start..], t))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use nom::bytes::complete::tag;
 
     #[test]
     fn delimited1() {
-        let res = delimited_paren(tag("hello"))("(hello)");
-        assert_eq!(res, Ok(("", "hello")));
+        let tokens = lex("(hello)");
+        let res = delimited_paren(token(TokenKind::Ident))(Input::new(&tokens));
+        assert_eq!(
+            res.map(|(rest, t)| (rest.peek().kind, t.text)),
+            Ok((TokenKind::Eof, "hello"))
+        );
     }
     #[test]
-    fn delimited2() {
-        let res = delimited_curly(tag("hello"))("{hello}");
-        assert_eq!(res, Ok(("", "hello")));
+    fn unclosed() {
+        let tokens = lex("(hello }");
+        let res = delimited_paren(token(TokenKind::Ident))(Input::new(&tokens));
+        match res {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(e.to_string(), "expected `)`, found `}`");
+                assert_eq!(e.span.start, 7);
+            }
+            res => panic!("expected a failure, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn tuples() {
+        let parse = |src| {
+            let tokens = lex(src);
+            let (_, found) = parenthesised(token(TokenKind::Ident))(Input::new(&tokens)).unwrap();
+            match found {
+                Parenthesised::One(t) => Parenthesised::One(t.text.to_string()),
+                Parenthesised::Tuple(ts) => {
+                    Parenthesised::Tuple(ts.iter().map(|t| t.text.to_string()).collect())
+                }
+            }
+        };
+
+        assert_eq!(parse("(a)"), Parenthesised::One("a".to_string()));
+        assert_eq!(parse("()"), Parenthesised::Tuple(vec![]));
+        assert_eq!(parse("(a,)"), Parenthesised::Tuple(vec!["a".to_string()]));
+        assert_eq!(
+            parse("(a, b,)"),
+            Parenthesised::Tuple(vec!["a".to_string(), "b".to_string()])
+        );
+    }
+
+    #[test]
+    fn spans() {
+        let tokens = lex("  hello");
+        let (_, (_, span)) = spanned(token(TokenKind::Ident))(Input::new(&tokens)).unwrap();
+        assert_eq!((span.start, span.end), (2, 7));
     }
 }
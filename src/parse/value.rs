@@ -1,71 +1,469 @@
-use crate::parse::Parse;
-use nom::IResult;
+use crate::parse::{
+    lexer::{number_body, radix},
+    util::token,
+    Error, Expected, Input, Parse, ParseResult, Span, TokenKind,
+};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Value {
     Boolean(bool),
-    Integer(i64),
-    Float(f64),
+    Integer(u64, Option<Suffix>),
+    Float(f64, Option<Suffix>),
+    String(String),
+    Char(char),
 }
 
 impl std::cmp::Eq for Value {}
 
+/// The type a number literal is given by a suffix, as in `42u8`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Suffix {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+}
+
+const SUFFIXES: &[(&str, Suffix)] = &[
+    ("i8", Suffix::I8),
+    ("i16", Suffix::I16),
+    ("i32", Suffix::I32),
+    ("i64", Suffix::I64),
+    ("u8", Suffix::U8),
+    ("u16", Suffix::U16),
+    ("u32", Suffix::U32),
+    ("u64", Suffix::U64),
+    ("f32", Suffix::F32),
+    ("f64", Suffix::F64),
+];
+
+impl Suffix {
+    pub fn name(self) -> &'static str {
+        SUFFIXES.iter().find(|(_, s)| *s == self).unwrap().0
+    }
+
+    /// The suffix spelled like the type `name`, if it is a number type.
+    pub fn from_name(name: &str) -> Option<Suffix> {
+        SUFFIXES.iter().find(|(n, _)| *n == name).map(|(_, s)| *s)
+    }
+
+    pub fn is_signed(self) -> bool {
+        matches!(self, Suffix::I8 | Suffix::I16 | Suffix::I32 | Suffix::I64)
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, Suffix::F32 | Suffix::F64)
+    }
+
+    /// The largest integer literal of this type.
+    pub fn max(self) -> u64 {
+        match self {
+            Suffix::I8 => i8::MAX as u64,
+            Suffix::I16 => i16::MAX as u64,
+            Suffix::I32 => i32::MAX as u64,
+            Suffix::I64 => i64::MAX as u64,
+            Suffix::U8 => u8::MAX as u64,
+            Suffix::U16 => u16::MAX as u64,
+            Suffix::U32 => u32::MAX as u64,
+            Suffix::U64 | Suffix::F32 | Suffix::F64 => u64::MAX,
+        }
+    }
+}
+
 impl Parse for Value {
-    fn parse(input: &str) -> IResult<&str, Self> {
+    fn parse(input: Input) -> ParseResult<Self> {
         use nom::{branch::alt, combinator::map};
         alt((
             map(parse_bool, Value::Boolean),
-            map(parse_float, Value::Float),
-            map(parse_int, Value::Integer),
+            parse_number,
+            map(parse_string, Value::String),
+            map(parse_char, Value::Char),
         ))(input)
     }
 }
 
-fn parse_bool(input: &str) -> IResult<&str, bool> {
-    use nom::{branch::alt, bytes::complete::tag, combinator::map};
-    alt((map(tag("false"), |_| false), map(tag("true"), |_| true)))(input)
+fn parse_bool(input: Input) -> ParseResult<bool> {
+    use nom::{branch::alt, combinator::map};
+    alt((
+        map(token(TokenKind::False), |_| false),
+        map(token(TokenKind::True), |_| true),
+    ))(input)
+}
+
+fn parse_number(input: Input) -> ParseResult<Value> {
+    number_token(input, false)
+}
+
+/// A number right after a `-`. Signed types have one more negative value
+/// than positive ones, so `-128i8` is fine although `128i8` isn't.
+pub(super) fn parse_negated_number(input: Input) -> ParseResult<Value> {
+    number_token(input, true)
 }
-fn parse_int(input: &str) -> IResult<&str, i64> {
-    use nom::{bytes::complete::take_while1, combinator::map};
-    fn is_digit(c: char) -> bool {
-        c >= '0' && c <= '9'
+
+fn number_token(input: Input, negated: bool) -> ParseResult<Value> {
+    use nom::branch::alt;
+
+    let (rest, t) = alt((token(TokenKind::Integer), token(TokenKind::Float)))(input)?;
+    match number(t.text, t.span.start, negated) {
+        Ok(value) => Ok((rest, value)),
+        Err(e) => Err(nom::Err::Failure(e)),
     }
+}
+
+/// The value of the number literal `text`, which starts at `offset` in the
+/// source, `negated` if a `-` comes right before it.
+fn number(text: &str, offset: usize, negated: bool) -> Result<Value, Error> {
+    let error = |start: usize, end: usize, expected| Error {
+        span: Span::new(offset + start, offset + end),
+        expected: vec![expected],
+        found: Some(text[start..end].to_string()),
+        context: None,
+    };
+
+    let (suffix_text, kind) = number_body(text).expect("number tokens start with a number");
+    let body = &text[..text.len() - suffix_text.len()];
+    let radix = radix(body.get(..2).unwrap_or(""));
+
+    let suffix = match SUFFIXES.iter().find(|(name, _)| *name == suffix_text) {
+        Some((_, suffix)) => Some(*suffix),
+        None if suffix_text.is_empty() => None,
+        None if suffix_text.starts_with(|c: char| c.is_ascii_digit()) => {
+            return Err(error(body.len(), body.len() + 1, Expected::Digit(radix)))
+        }
+        None => return Err(error(body.len(), text.len(), Expected::Suffix)),
+    };
+
+    let digits: String = body[if radix == 10 { 0 } else { 2 }..]
+        .chars()
+        .filter(|c| *c != '_')
+        .collect();
 
-    map(take_while1(is_digit), |s: &str| s.parse::<i64>().unwrap())(input)
+    if kind == TokenKind::Float || suffix.is_some_and(Suffix::is_float) {
+        let ty = match suffix {
+            None => Suffix::F64,
+            Some(suffix) if suffix.is_float() && radix == 10 => suffix,
+            Some(_) => return Err(error(body.len(), text.len(), Expected::Suffix)),
+        };
+        let value = digits.parse::<f64>().unwrap();
+        let finite = match ty {
+            Suffix::F32 => (value as f32).is_finite(),
+            _ => value.is_finite(),
+        };
+
+        return if finite {
+            Ok(Value::Float(value, suffix))
+        } else {
+            Err(error(0, text.len(), Expected::InRange(ty.name())))
+        };
+    }
+
+    if digits.is_empty() {
+        return Err(error(0, text.len(), Expected::Digit(radix)));
+    }
+    let ty = suffix.unwrap_or(Suffix::U64);
+    let max = match ty.is_signed() && negated {
+        true => ty.max() + 1,
+        false => ty.max(),
+    };
+    match u64::from_str_radix(&digits, radix) {
+        Ok(value) if value <= max => Ok(Value::Integer(value, suffix)),
+        _ => Err(error(0, text.len(), Expected::InRange(ty.name()))),
+    }
 }
 
-fn parse_float(input: &str) -> IResult<&str, f64> {
-    use nom::{character::complete::char, combinator::map, sequence::separated_pair};
+fn parse_string(input: Input) -> ParseResult<String> {
+    use nom::branch::alt;
 
-    map(separated_pair(parse_int, char('.'), parse_int), |(a, b)| {
-        format!("{}.{}", a, b).parse::<f64>().unwrap()
-    })(input)
+    let (rest, t) = alt((token(TokenKind::Str), token(TokenKind::RawStr)))(input)?;
+    let value = match t.kind {
+        TokenKind::RawStr => {
+            let quote = t.text.find('"').unwrap();
+            t.text[quote + 1..t.text.len() - quote].to_string()
+        }
+        _ => unescape(&t.text[1..t.text.len() - 1], t.span.start + 1)?,
+    };
+
+    Ok((rest, value))
+}
+
+fn parse_char(input: Input) -> ParseResult<char> {
+    let (rest, t) = token(TokenKind::Char)(input)?;
+    let value = unescape(&t.text[1..t.text.len() - 1], t.span.start + 1)?;
+
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok((rest, c)),
+        _ => Err(nom::Err::Failure(Error {
+            span: t.span,
+            expected: vec![Expected::Char],
+            found: Some(t.text.to_string()),
+            context: None,
+        })),
+    }
+}
+
+/// Resolves the escapes in the body of a string or char literal,
+/// `offset` being where `body` starts in the source.
+fn unescape(body: &str, offset: usize) -> Result<String, nom::Err<Error>> {
+    let mut value = String::new();
+    let mut rest = body;
+
+    while let Some(i) = rest.find('\\') {
+        value.push_str(&rest[..i]);
+        let escape = &rest[i..];
+
+        match escaped_char(escape) {
+            Ok((c, len)) => {
+                value.push(c);
+                rest = &escape[len..];
+            }
+            Err(len) => {
+                let start = offset + body.len() - escape.len();
+                return Err(nom::Err::Failure(Error {
+                    span: Span::new(start, start + len),
+                    expected: vec![Expected::Escape],
+                    found: Some(escape[..len].to_string()),
+                    context: None,
+                }));
+            }
+        }
+    }
+
+    value.push_str(rest);
+    Ok(value)
+}
+
+/// The character the escape at the start of `s` stands for and the length
+/// of the escape, or the length of the invalid escape.
+fn escaped_char(s: &str) -> Result<(char, usize), usize> {
+    let mut chars = s[1..].chars();
+    let c = match chars.next() {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('0') => '\0',
+        Some('\\') => '\\',
+        Some('"') => '"',
+        Some('\'') => '\'',
+        Some('u') => return unicode_escape(s),
+        Some(c) => return Err(1 + c.len_utf8()),
+        None => return Err(1),
+    };
+
+    Ok((c, 2))
+}
+
+/// `\u{...}` with one to six hex digits.
+fn unicode_escape(s: &str) -> Result<(char, usize), usize> {
+    if !s[2..].starts_with('{') {
+        return Err(2);
+    }
+    let digits = s[3..]
+        .find(|c: char| !c.is_ascii_hexdigit())
+        .map_or(s.len(), |i| i + 3);
+    if !s[digits..].starts_with('}') {
+        return Err(digits);
+    }
+
+    let len = digits + 1;
+    match &s[3..digits] {
+        hex if (1..=6).contains(&hex.len()) => u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(std::char::from_u32)
+            .map(|c| (c, len))
+            .ok_or(len),
+        _ => Err(len),
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::parse::util::parse_str;
+
+    fn number(src: &str) -> Result<Value, String> {
+        match parse_str(parse_number, src) {
+            Ok(("", value)) => Ok(value),
+            Ok((rest, _)) => panic!("{} was left over", rest),
+            Err(nom::Err::Failure(e)) => Err(e.to_string()),
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+    }
 
     #[test]
     fn bool() {
-        assert!(parse_int("").is_err());
-        assert_eq!(parse_bool("true"), Ok(("", true)));
-        assert_eq!(parse_bool("false"), Ok(("", false)));
+        assert!(parse_str(parse_bool, "").is_err());
+        assert_eq!(parse_str(parse_bool, "true"), Ok(("", true)));
+        assert_eq!(parse_str(parse_bool, "false"), Ok(("", false)));
     }
 
     #[test]
     fn int() {
-        assert!(parse_int("").is_err());
-        assert_eq!(parse_int("0"), Ok(("", 0)));
-        assert_eq!(parse_int("123"), Ok(("", 123)));
-        assert_eq!(parse_int("987654321"), Ok(("", 987654321)));
+        assert!(parse_str(parse_number, "").is_err());
+        assert_eq!(number("0"), Ok(Value::Integer(0, None)));
+        assert_eq!(number("123"), Ok(Value::Integer(123, None)));
+        assert_eq!(number("987654321"), Ok(Value::Integer(987654321, None)));
+        assert_eq!(number("1_000_000"), Ok(Value::Integer(1000000, None)));
+        assert_eq!(
+            number("18446744073709551615"),
+            Ok(Value::Integer(u64::MAX, None))
+        );
+    }
+
+    #[test]
+    fn radix() {
+        assert_eq!(number("0xff"), Ok(Value::Integer(255, None)));
+        assert_eq!(number("0xDead_Beef"), Ok(Value::Integer(0xdead_beef, None)));
+        assert_eq!(number("0o17"), Ok(Value::Integer(15, None)));
+        assert_eq!(number("0b1010_1010"), Ok(Value::Integer(170, None)));
+        assert_eq!(
+            number("0b102"),
+            Err("expected binary digit, found `2`".to_string())
+        );
+        assert_eq!(
+            number("0x"),
+            Err("expected hex digit, found `0x`".to_string())
+        );
     }
 
     #[test]
     fn float() {
-        assert!(parse_int("").is_err());
-        assert_eq!(parse_float("0.0"), Ok(("", 0.0)));
-        assert_eq!(parse_float("0.000000"), Ok(("", 0.0)));
-        assert_eq!(parse_float("123.456"), Ok(("", 123.456)));
+        assert_eq!(number("0.0"), Ok(Value::Float(0.0, None)));
+        assert_eq!(number("0.000000"), Ok(Value::Float(0.0, None)));
+        assert_eq!(number("123.456"), Ok(Value::Float(123.456, None)));
+        assert_eq!(number("0.05"), Ok(Value::Float(0.05, None)));
+        assert_eq!(number("1_000.000_5"), Ok(Value::Float(1000.0005, None)));
+        assert_eq!(number("1e-9"), Ok(Value::Float(1e-9, None)));
+        assert_eq!(number("2.5E+3"), Ok(Value::Float(2500.0, None)));
+    }
+
+    #[test]
+    fn suffixes() {
+        assert_eq!(number("42u8"), Ok(Value::Integer(42, Some(Suffix::U8))));
+        assert_eq!(
+            number("0xffi32"),
+            Ok(Value::Integer(255, Some(Suffix::I32)))
+        );
+        assert_eq!(number("1.0f32"), Ok(Value::Float(1.0, Some(Suffix::F32))));
+        assert_eq!(number("7f64"), Ok(Value::Float(7.0, Some(Suffix::F64))));
+        assert_eq!(
+            number("42abc"),
+            Err("expected type suffix, found `abc`".to_string())
+        );
+        assert_eq!(
+            number("1.5u8"),
+            Err("expected type suffix, found `u8`".to_string())
+        );
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert_eq!(
+            number("256u8"),
+            Err("expected value in range of `u8`, found `256u8`".to_string())
+        );
+        assert_eq!(
+            number("128i8"),
+            Err("expected value in range of `i8`, found `128i8`".to_string())
+        );
+        assert_eq!(
+            number("18446744073709551616"),
+            Err("expected value in range of `u64`, found `18446744073709551616`".to_string())
+        );
+        assert_eq!(
+            number("1e39f32"),
+            Err("expected value in range of `f32`, found `1e39f32`".to_string())
+        );
+        assert_eq!(
+            number("1e999"),
+            Err("expected value in range of `f64`, found `1e999`".to_string())
+        );
+    }
+
+    #[test]
+    fn negated() {
+        let negated = |src| match parse_str(parse_negated_number, src) {
+            Ok((_, value)) => Ok(value),
+            Err(nom::Err::Failure(e)) => Err(e.to_string()),
+            Err(e) => panic!("unexpected error {:?}", e),
+        };
+
+        assert_eq!(negated("128i8"), Ok(Value::Integer(128, Some(Suffix::I8))));
+        assert_eq!(
+            negated("9223372036854775808i64"),
+            Ok(Value::Integer(1 << 63, Some(Suffix::I64)))
+        );
+        assert_eq!(
+            negated("129i8"),
+            Err("expected value in range of `i8`, found `129i8`".to_string())
+        );
+        assert_eq!(
+            negated("256u8"),
+            Err("expected value in range of `u8`, found `256u8`".to_string())
+        );
+    }
+
+    #[test]
+    fn string() {
+        let string = |src| parse_str(parse_string, src).map(|(rest, s)| (rest, s.to_string()));
+
+        assert_eq!(string(r#""""#), Ok(("", "".to_string())));
+        assert_eq!(string(r#""hello""#), Ok(("", "hello".to_string())));
+        assert_eq!(
+            string(r#""a\n\t\"\\\u{e9}\u{1F600}""#),
+            Ok(("", "a\n\t\"\\é😀".to_string()))
+        );
+        assert_eq!(
+            string(r##"r"C:\no\escapes""##),
+            Ok(("", r"C:\no\escapes".to_string()))
+        );
+        assert_eq!(
+            string(r###"r#"say "hi""#"###),
+            Ok(("", r#"say "hi""#.to_string()))
+        );
+    }
+
+    #[test]
+    fn invalid_escape() {
+        let error = |src| match parse_str(parse_string, src) {
+            Err(nom::Err::Failure(e)) => (e.to_string(), e.span.start),
+            res => panic!("expected a failure, got {:?}", res),
+        };
+
+        assert_eq!(
+            error(r#""ab\qc""#),
+            ("expected escape sequence, found `\\q`".to_string(), 3)
+        );
+        assert_eq!(
+            error(r#""\u{110000}""#),
+            (
+                "expected escape sequence, found `\\u{110000}`".to_string(),
+                1
+            )
+        );
+        assert_eq!(
+            error(r#""\u{zz}""#),
+            ("expected escape sequence, found `\\u{`".to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn char() {
+        assert_eq!(parse_str(parse_char, "'a'"), Ok(("", 'a')));
+        assert_eq!(parse_str(parse_char, r"'\''"), Ok(("", '\'')));
+        assert_eq!(parse_str(parse_char, r"'\u{3bb}'"), Ok(("", 'λ')));
+
+        match parse_str(parse_char, "'ab'") {
+            Err(nom::Err::Failure(e)) => {
+                assert_eq!(e.to_string(), "expected single character, found `'ab'`")
+            }
+            res => panic!("expected a failure, got {:?}", res),
+        }
     }
 }